@@ -0,0 +1,95 @@
+use std::path::Path;
+use tracing::debug;
+
+use crate::models::ProcessedFile;
+
+/// Tag for lines that are not in any commit yet, as wide as a real tag
+const UNCOMMITTED_TAG: &str = "------- ----------";
+
+/// Prefix each line of the first `max_files` files found under `input_path`
+/// with the short hash and date of the commit that last touched it, e.g.
+/// `1a2b3c4 2024-05-01 | fn main() {}`. Blame runs against each file's
+/// current content, so uncommitted lines get a placeholder tag. Files outside
+/// git, untracked files and files past the limit are left plain.
+pub fn annotate_with_blame(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    max_files: usize,
+) -> Vec<ProcessedFile> {
+    let Ok(repo) = git2::Repository::discover(input_path) else {
+        return files;
+    };
+    let Some(workdir) = repo.workdir().and_then(|dir| dir.canonicalize().ok()) else {
+        return files;
+    };
+    let base_dir = if input_path.is_dir() {
+        input_path
+    } else {
+        input_path.parent().unwrap_or(Path::new("."))
+    };
+
+    let mut blamed = 0;
+    files
+        .into_iter()
+        .map(|file| {
+            if blamed >= max_files {
+                return file;
+            }
+            let Some(repo_path) = base_dir
+                .join(&file.rel_path)
+                .canonicalize()
+                .ok()
+                .and_then(|abs| abs.strip_prefix(&workdir).ok().map(Path::to_path_buf))
+            else {
+                return file;
+            };
+            let committed = match repo.blame_file(&repo_path, None) {
+                Ok(blame) => blame,
+                Err(e) => {
+                    debug!("No blame for {}: {}", file.rel_path, e);
+                    return file;
+                }
+            };
+            let Ok(blame) = committed.blame_buffer(file.content.as_bytes()) else {
+                return file;
+            };
+            blamed += 1;
+
+            let content = file
+                .content
+                .split_inclusive('\n')
+                .enumerate()
+                .map(|(index, line)| {
+                    let tag = blame
+                        .get_line(index + 1)
+                        .filter(|hunk| !hunk.final_commit_id().is_zero())
+                        .and_then(|hunk| blame_tag(&repo, hunk.final_commit_id()))
+                        .unwrap_or_else(|| UNCOMMITTED_TAG.to_string());
+                    format!("{} | {}", tag, line)
+                })
+                .collect();
+            ProcessedFile::new_with_category(
+                file.rel_path,
+                content,
+                file.priority,
+                file.file_index,
+                file.category,
+            )
+        })
+        .collect()
+}
+
+/// `<short hash> <YYYY-MM-DD>` of a commit
+fn blame_tag(repo: &git2::Repository, oid: git2::Oid) -> Option<String> {
+    let commit = repo.find_commit(oid).ok()?;
+    let date = time::OffsetDateTime::from_unix_timestamp(commit.time().seconds())
+        .ok()?
+        .date();
+    Some(format!(
+        "{:.7} {:04}-{:02}-{:02}",
+        oid.to_string(),
+        date.year(),
+        u8::from(date.month()),
+        date.day()
+    ))
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Bumped whenever the cache file layout changes; older files are ignored
+const CACHE_VERSION: u32 = 1;
+
+/// What a previous run learned about a file, valid while its mtime and size
+/// are unchanged, or for a file with a new mtime but the same size and hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Modification time in nanoseconds since the Unix epoch
+    pub mtime: u64,
+    pub size: u64,
+    /// False for files skipped as binary
+    pub is_text: bool,
+    /// SHA-256 of the raw file bytes, hex-encoded
+    pub content_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    entries: HashMap<String, CacheEntry>,
+}
+
+/// On-disk cache of per-file results for one input directory, stored as
+/// `<cache_dir>/<hash of the directory>.json`. Only files seen during the
+/// run are written back, so entries for deleted files don't pile up.
+pub struct FileCache {
+    path: PathBuf,
+    previous: HashMap<String, CacheEntry>,
+    current: Mutex<HashMap<String, CacheEntry>>,
+}
+
+impl FileCache {
+    /// Load the cache for `base_path`. A missing, unreadable or outdated
+    /// cache file just starts out empty.
+    pub fn open(cache_dir: &Path, base_path: &Path) -> Self {
+        let base = base_path
+            .canonicalize()
+            .unwrap_or_else(|_| base_path.to_path_buf());
+        let key = hex(&Sha256::digest(base.to_string_lossy().as_bytes()));
+        let path = cache_dir.join(format!("{}.json", &key[..16]));
+
+        let previous = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<CacheFile>(&bytes).ok())
+            .filter(|file| file.version == CACHE_VERSION)
+            .map(|file| file.entries)
+            .unwrap_or_default();
+        Self {
+            path,
+            previous,
+            current: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The entry for `rel_path` if it was recorded with the same size. Its
+    /// verdict holds if the mtime also matches, or else the content hash.
+    pub fn lookup(&self, rel_path: &str, size: u64) -> Option<&CacheEntry> {
+        self.previous
+            .get(rel_path)
+            .filter(|entry| entry.size == size)
+    }
+
+    /// Remember `entry` for `rel_path` in this run's cache
+    pub fn record(&self, rel_path: &str, entry: CacheEntry) {
+        self.current
+            .lock()
+            .unwrap()
+            .insert(rel_path.to_string(), entry);
+    }
+
+    /// Write this run's entries to disk, creating the cache directory if needed
+    pub fn save(&self) -> Result<()> {
+        let entries = self.current.lock().unwrap().clone();
+        let json = serde_json::to_vec(&CacheFile {
+            version: CACHE_VERSION,
+            entries,
+        })?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| anyhow!("cache_dir: cannot create '{}': {}", dir.display(), e))?;
+        }
+        fs::write(&self.path, json)
+            .map_err(|e| anyhow!("cache_dir: cannot write '{}': {}", self.path.display(), e))
+    }
+}
+
+/// Modification time of `modified` in nanoseconds since the Unix epoch
+pub fn mtime_nanos(modified: SystemTime) -> u64 {
+    modified
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Hex-encoded SHA-256 of `content`
+pub fn content_hash(content: &[u8]) -> String {
+    hex(&Sha256::digest(content))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,237 @@
+/// Comment syntax of a language family, as far as stripping needs it
+struct Syntax {
+    /// Markers that comment out the rest of the line
+    line: &'static [&'static str],
+    /// Block comment delimiters
+    block: Option<(&'static str, &'static str)>,
+    /// Block comments nest (Rust)
+    nested_blocks: bool,
+    /// Characters that open and close a string literal
+    quotes: &'static [char],
+    /// `'` only opens a short char literal, so lifetimes like `'a` aren't strings (Rust)
+    char_literals: bool,
+    /// `"""` and `'''` open strings that may span lines (Python, TOML)
+    triple_quotes: bool,
+    /// A line comment needs whitespace (or the line start) before it, so `$#` isn't one (shell, YAML)
+    comment_after_space: bool,
+    /// Quotes only open a string at the start of a value, so `it's` isn't one (YAML)
+    quotes_at_token_start: bool,
+}
+
+const C_FAMILY: Syntax = Syntax {
+    line: &["//"],
+    block: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &['"', '\'', '`'],
+    char_literals: false,
+    triple_quotes: false,
+    comment_after_space: false,
+    quotes_at_token_start: false,
+};
+
+const RUST: Syntax = Syntax {
+    nested_blocks: true,
+    quotes: &['"'],
+    char_literals: true,
+    ..C_FAMILY
+};
+
+const HASH: Syntax = Syntax {
+    line: &["#"],
+    block: None,
+    nested_blocks: false,
+    quotes: &['"', '\''],
+    char_literals: false,
+    triple_quotes: true,
+    comment_after_space: false,
+    quotes_at_token_start: false,
+};
+
+const SHELL: Syntax = Syntax {
+    triple_quotes: false,
+    comment_after_space: true,
+    ..HASH
+};
+
+const YAML: Syntax = Syntax {
+    triple_quotes: false,
+    comment_after_space: true,
+    quotes_at_token_start: true,
+    ..HASH
+};
+
+const SQL: Syntax = Syntax {
+    line: &["--"],
+    block: Some(("/*", "*/")),
+    nested_blocks: false,
+    quotes: &['\'', '"'],
+    char_literals: false,
+    triple_quotes: false,
+    comment_after_space: false,
+    quotes_at_token_start: false,
+};
+
+fn syntax_for(ext: &str) -> Option<&'static Syntax> {
+    match ext {
+        "rs" => Some(&RUST),
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "cs" | "java" | "kt" | "kts"
+        | "scala" | "swift" | "go" | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "php"
+        | "dart" => Some(&C_FAMILY),
+        "py" | "rb" | "pl" | "r" | "toml" => Some(&HASH),
+        "yaml" | "yml" => Some(&YAML),
+        "sh" | "bash" | "zsh" => Some(&SHELL),
+        "sql" => Some(&SQL),
+        _ => None,
+    }
+}
+
+/// Remove comments from `content` based on the file extension `ext`: `//`
+/// and `/* */` for C-family languages, `#` for Python, shell, TOML and YAML,
+/// `--` and `/* */` for SQL. String literals are left alone, as is a leading
+/// `#!` line. Lines left empty by the removal are dropped; unknown extensions
+/// are returned unchanged.
+pub fn strip_comments_for(content: &str, ext: &str) -> String {
+    let Some(syntax) = syntax_for(&ext.to_ascii_lowercase()) else {
+        return content.to_string();
+    };
+    tidy_lines(content, &strip(content, syntax))
+}
+
+fn strip(content: &str, syntax: &Syntax) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+
+    if syntax.line.contains(&"#") && rest.starts_with("#!") {
+        let end = rest.find('\n').unwrap_or(rest.len());
+        out.push_str(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    let mut prev: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        let string_allowed = !syntax.quotes_at_token_start
+            || prev.is_none_or(|p| p.is_whitespace() || "[{(,:=-".contains(p));
+        if let Some(len) = string_literal_len(rest, syntax).filter(|_| string_allowed) {
+            out.push_str(&rest[..len]);
+            prev = rest[..len].chars().next_back();
+            rest = &rest[len..];
+            continue;
+        }
+
+        let comment_allowed = !syntax.comment_after_space || prev.is_none_or(char::is_whitespace);
+        if comment_allowed && syntax.line.iter().any(|marker| rest.starts_with(marker)) {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        }
+
+        if let Some((open, close)) = syntax.block {
+            if rest.starts_with(open) {
+                let len = block_comment_len(rest, open, close, syntax.nested_blocks);
+                // Keep the line breaks so stripped lines still line up with the original
+                out.extend(rest[..len].chars().filter(|&c| c == '\n'));
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        out.push(c);
+        prev = Some(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Length of the string literal `rest` starts with, if any. Unterminated
+/// single-line strings end at the line break.
+fn string_literal_len(rest: &str, syntax: &Syntax) -> Option<usize> {
+    if syntax.triple_quotes {
+        for triple in ["\"\"\"", "'''"] {
+            if let Some(body) = rest.strip_prefix(triple) {
+                return Some(body.find(triple).map_or(rest.len(), |end| end + 6));
+            }
+        }
+    }
+
+    let quote = rest.chars().next()?;
+    if syntax.char_literals && quote == '\'' {
+        return char_literal_len(rest);
+    }
+    if !syntax.quotes.contains(&quote) {
+        return None;
+    }
+
+    let mut chars = rest.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '\n' if quote != '`' => return Some(i),
+            c if c == quote => return Some(i + c.len_utf8()),
+            _ => {}
+        }
+    }
+    Some(rest.len())
+}
+
+/// Length of a Rust char literal like `'x'` or `'\n'`; None for lifetimes
+fn char_literal_len(rest: &str) -> Option<usize> {
+    let body = &rest[1..];
+    let mut chars = body.char_indices();
+    match chars.next()? {
+        (_, '\\') => body
+            .char_indices()
+            .skip(2)
+            .take(10)
+            .take_while(|&(_, c)| c != '\n')
+            .find(|&(_, c)| c == '\'')
+            .map(|(i, _)| i + 2),
+        (_, '\'') | (_, '\n') => None,
+        _ => match chars.next()? {
+            (i, '\'') => Some(i + 2),
+            _ => None,
+        },
+    }
+}
+
+fn block_comment_len(rest: &str, open: &str, close: &str, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i..].starts_with(open) && (nested || depth == 0) {
+            depth += 1;
+            i += open.len();
+        } else if rest[i..].starts_with(close) {
+            depth -= 1;
+            i += close.len();
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += rest[i..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    rest.len()
+}
+
+/// Compare stripped lines with the originals: unchanged lines stay as they
+/// were, changed lines lose trailing whitespace, and lines that only held a
+/// comment are dropped.
+fn tidy_lines(original: &str, stripped: &str) -> String {
+    original
+        .split('\n')
+        .zip(stripped.split('\n'))
+        .filter_map(|(before, after)| {
+            if before == after {
+                return Some(after.to_string());
+            }
+            let trimmed = after.trim_end();
+            if trimmed.is_empty() && !before.trim().is_empty() {
+                return None;
+            }
+            let cr = if before.ends_with('\r') { "\r" } else { "" };
+            Some(format!("{}{}", trimmed, cr))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
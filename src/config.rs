@@ -1,12 +1,24 @@
 use anyhow::{anyhow, Result};
 use bytesize::ByteSize;
-use clap_config_file::ClapConfigFile;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches};
 use sha2::{Digest, Sha256};
 use std::io::{self, BufRead, BufReader, IsTerminal};
-use std::{fs, path::Path, process::Command, str::FromStr, time::UNIX_EPOCH};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
 
 use crate::{
-    defaults::{BINARY_FILE_EXTENSIONS, DEFAULT_IGNORE_PATTERNS, DEFAULT_OUTPUT_TEMPLATE},
+    defaults::{
+        BINARY_FILE_EXTENSIONS, COMPRESSIONS, DEFAULT_IGNORE_PATTERNS,
+        DEFAULT_MAX_REPLACEMENT_RATIO, DEFAULT_OUTPUT_TEMPLATE, GIT_BOOST_STRATEGIES,
+        MODEL_CONTEXT_WINDOWS, OUTPUT_FORMATS, OVERSIZE_BEHAVIORS, PRIORITY_ORDERS,
+        PROJECT_ROOT_MARKERS, SIZE_DISPLAYS,
+    },
     priority::PriorityRule,
 };
 
@@ -18,97 +30,408 @@ pub enum ConfigFormat {
     Json,
 }
 
-#[derive(ClapConfigFile, Clone)]
-#[config_file_name = "yek"]
-#[config_file_formats = "toml,yaml,json"]
+#[derive(clap::Args, Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
 pub struct YekConfig {
     /// Input files and/or directories to process
-    #[config_arg(positional)]
+    #[arg(value_name = "PATH")]
     pub input_paths: Vec<String>,
 
     /// Print version of yek
-    #[config_arg(long = "version", short = 'V')]
+    #[arg(long = "version", short = 'V')]
     pub version: bool,
 
     /// Update yek to the latest version
-    #[config_arg(long = "update")]
+    #[arg(long = "update")]
     pub update: bool,
 
     /// Max size per chunk. e.g. "10MB" or "128K" or when using token counting mode, "100" or "128K"
-    #[config_arg(default_value = "10MB")]
+    #[arg(long, default_value = "10MB")]
     pub max_size: String,
 
     /// Use token mode instead of byte mode
-    #[config_arg()]
+    #[arg(long, default_value = "", hide_default_value = true)]
     pub tokens: String,
 
+    /// Pack every file into one output, ignoring `max_size` and `tokens`
+    #[arg(long = "single-file")]
+    pub single_file: bool,
+
     /// Enable JSON output
-    #[config_arg()]
+    #[arg(long)]
     pub json: bool,
 
     /// Enable debug output
-    #[config_arg()]
+    #[arg(long)]
     pub debug: bool,
 
     /// Include line numbers in output
-    #[config_arg(long = "line-numbers")]
+    #[arg(long = "line-numbers")]
     pub line_numbers: bool,
 
     /// Output directory. If none is provided & stdout is a TTY, we pick a temp dir
-    #[config_arg()]
+    #[arg(long)]
     pub output_dir: Option<String>,
 
     /// Output filename. If provided, write output to this file in current directory
-    #[config_arg(long = "output-name")]
+    #[arg(long = "output-name")]
     pub output_name: Option<String>,
 
     /// Output template. Defaults to ">>>> FILE_PATH\nFILE_CONTENT"
-    #[config_arg()]
+    #[arg(long)]
     pub output_template: Option<String>,
 
     /// Ignore patterns
-    #[config_arg(long = "ignore-patterns", multi_value_behavior = "extend")]
+    #[arg(long = "ignore-patterns", num_args = 1..)]
     pub ignore_patterns: Vec<String>,
 
     /// Unignore patterns. Yek has some built-in ignore patterns, but you can override them here.
-    #[config_arg(long = "unignore-patterns", multi_value_behavior = "extend")]
+    #[arg(long = "unignore-patterns", num_args = 1..)]
     pub unignore_patterns: Vec<String>,
 
+    /// Include patterns. When set, only files matching at least one (and not ignored) are packed
+    #[arg(long = "include-patterns", num_args = 1..)]
+    pub include_patterns: Vec<String>,
+
+    /// Directory names (or paths relative to the input) to prune from the walk entirely
+    #[arg(long = "exclude-dir", num_args = 1..)]
+    pub exclude_dir: Vec<String>,
+
+    /// Languages to skip, as detected from file names and extensions (e.g. "json", "lock", "dts")
+    #[arg(long = "exclude-languages", num_args = 1..)]
+    pub exclude_languages: Vec<String>,
+
     /// Priority rules
-    #[config_arg(accept_from = "config_only")]
+    #[arg(skip)]
     pub priority_rules: Vec<PriorityRule>,
 
+    /// CSV (`path,score`) or JSON (`{"path": score}`) file whose scores set file priorities directly
+    #[arg(long = "priority-scores-file")]
+    pub priority_scores_file: Option<String>,
+
     /// Binary file extensions to ignore
-    #[config_arg(accept_from = "config_only", default_value = BINARY_FILE_EXTENSIONS)]
+    #[arg(skip)]
     pub binary_extensions: Vec<String>,
 
-    /// Maximum additional boost from Git commit times (0..1000)
-    #[config_arg(accept_from = "config_only")]
+    /// Maximum additional boost from Git commit times (0..1000); 0 disables git boosting
+    #[arg(skip)]
     pub git_boost_max: Option<i32>,
 
+    /// How the git recency boost combines with rule scores: "add" (default), "max" or "weighted"
+    #[arg(long = "git-boost-strategy")]
+    pub git_boost_strategy: Option<String>,
+
+    /// Share of the git boost with the "weighted" strategy (0..1); rule scores get the rest
+    #[arg(long = "git-boost-weight", default_value = "0.5")]
+    pub git_boost_weight: f64,
+
     /// Category-based priority weights
-    #[config_arg(accept_from = "config_only")]
+    #[arg(skip)]
     pub category_weights: Option<crate::category::CategoryWeights>,
 
     /// Include directory tree header in output (incompatible with JSON output)
-    #[config_arg(long = "tree-header", short = 't')]
+    #[arg(long = "tree-header", short = 't')]
     pub tree_header: bool,
 
     /// Show only the directory tree (no file contents, incompatible with JSON output)
-    #[config_arg(long = "tree-only")]
+    #[arg(long = "tree-only")]
     pub tree_only: bool,
 
+    /// Output formats to write in a single run (text, markdown, json, embeddings)
+    #[arg(long = "formats", num_args = 1..)]
+    pub formats: Vec<String>,
+
+    /// Only pack the N "largest" or "smallest" files by content size (see select_count)
+    #[arg(long)]
+    pub select: Option<String>,
+
+    /// Number of files to keep when `select` is set
+    #[arg(long = "select-count")]
+    pub select_count: Option<usize>,
+
+    /// Keep at most this many files, dropping the lowest-priority ones with a warning
+    #[arg(long = "max-files")]
+    pub max_files: Option<usize>,
+
+    /// Budget for all packed files together (e.g. "2MB", or "100k" in token mode); the lowest-priority files are dropped
+    #[arg(long = "max-total-size")]
+    pub max_total_size: Option<String>,
+
+    /// Directory for a cache of per-file results, so unchanged files are cheaper to process on the next run
+    #[arg(long = "cache-dir")]
+    pub cache_dir: Option<String>,
+
+    /// Banner emitted once at the top of the output. Supports {repo}, {timestamp}, {version} and {command}
+    #[arg(long)]
+    pub preamble: Option<String>,
+
+    /// Pack the unified diff of each changed file instead of its full content
+    #[arg(long = "diff-output")]
+    pub diff_output: bool,
+
+    /// (Experimental) Prefix each line with the short hash and date of the commit that last touched it
+    #[arg(long = "blame")]
+    pub blame: bool,
+
+    /// Blame at most this many files per input path; the rest are emitted plain
+    #[arg(long = "blame-max-files", default_value = "100")]
+    pub blame_max_files: usize,
+
+    /// Put each changed file's diff right before its full content
+    #[arg(long = "changed-with-diff")]
+    pub changed_with_diff: bool,
+
+    /// Only pack files changed by the commits in this range, e.g. "main..feature"
+    #[arg(long = "range")]
+    pub range: Option<String>,
+
+    /// Only pack files that differ between this ref and `git_diff_head`, like `git diff base...head`
+    #[arg(long = "git-diff-base")]
+    pub git_diff_base: Option<String>,
+
+    /// Head ref for `git_diff_base` (defaults to HEAD)
+    #[arg(long = "git-diff-head")]
+    pub git_diff_head: Option<String>,
+
+    /// Git ref to diff against when `diff_output` or `changed_with_diff` is set (defaults to HEAD)
+    #[arg(long)]
+    pub since: Option<String>,
+
+    /// Apply the .gitignore files as they were at this git ref instead of the working tree's
+    #[arg(long = "gitignore-at")]
+    pub gitignore_at: Option<String>,
+
+    /// Place each directory's README immediately before that directory's other files
+    #[arg(long = "docs-first-per-dir")]
+    pub docs_first_per_dir: bool,
+
+    /// Emit a "Dependency graph:" section of imports between packed Rust and JS/TS files
+    #[arg(long = "dep-graph")]
+    pub dep_graph: bool,
+
+    /// Report lines that look like secrets (API keys, tokens, private keys) to stderr
+    #[arg(long = "secret-scan")]
+    pub secret_scan: bool,
+
+    /// Replace built-in secret patterns (AWS keys, API_KEY=..., PEM private keys) with [REDACTED]
+    #[arg(long = "redact-secrets")]
+    pub redact_secrets: bool,
+
+    /// Regexes whose matches are replaced with [REDACTED] before output
+    #[arg(long = "redact-patterns", num_args = 1..)]
+    pub redact_patterns: Vec<String>,
+
+    /// Order of priorities in the output: "ascending" (most important last) or "descending"
+    #[arg(long = "priority-order", default_value = "ascending")]
+    pub priority_order: String,
+
+    /// Strip comments from known source languages (C-family, Rust, Python, shell, TOML, YAML, SQL) to save tokens
+    #[arg(long = "strip-comments")]
+    pub strip_comments: bool,
+
+    /// Emit files in path order instead of priority order
+    #[arg(long = "no-sort")]
+    pub no_sort: bool,
+
+    /// Put build/package manifests (Cargo.toml, package.json, ...) at the top of the output
+    #[arg(long = "manifests-first")]
+    pub manifests_first: bool,
+
+    /// (Experimental) Emit leading blocks shared by several files, such as license headers, only once
+    #[arg(long = "dedupe-boilerplate")]
+    pub dedupe_boilerplate: bool,
+
+    /// Group output by top-level directory, each section starting with that directory's tree
+    #[arg(long = "module-sections")]
+    pub module_sections: bool,
+
+    /// Drop files not changed within this age, e.g. "30d", "6w" or "1y" (git history, else mtime)
+    #[arg(long = "max-age")]
+    pub max_age: Option<String>,
+
+    /// Only consider git history since this ref (like `<ref>..HEAD`), date ("2024-05-01") or age ("30d")
+    #[arg(long = "git-since")]
+    pub git_since: Option<String>,
+
+    /// With git_since, drop files that have no commits in that window
+    #[arg(long = "git-since-filter")]
+    pub git_since_filter: bool,
+
+    /// With max_age, also drop files whose last change time is unknown
+    #[arg(long = "max-age-drop-unknown")]
+    pub max_age_drop_unknown: bool,
+
+    /// Drop files whose content is at least this similar (0.0..=1.0) to a higher-priority file
+    #[arg(long = "near-duplicate-threshold")]
+    pub near_duplicate_threshold: Option<f64>,
+
+    /// Emit identical file contents once, listing the other paths in the header
+    #[arg(long = "dedup-identical")]
+    pub dedup_identical: bool,
+
+    /// Don't detect common binaries (PNG, ELF, PDF, ZIP, gzip) by their leading magic bytes
+    #[arg(long = "no-magic-detection")]
+    pub no_magic_detection: bool,
+
+    /// Bytes sampled from the start of each file to decide whether it is binary (default 8192)
+    #[arg(long = "binary-detection-bytes")]
+    pub binary_detection_bytes: Option<usize>,
+
+    /// Encoding for files without a byte order mark that aren't valid UTF-8, e.g. "latin1"
+    #[arg(long = "encoding")]
+    pub encoding: Option<String>,
+
+    /// Fail instead of warning when a file disappears between the walk and the read
+    #[arg(long = "fail-on-missing")]
+    pub fail_on_missing: bool,
+
+    /// Fail on invalid configuration, such as priority rules that aren't valid regexes, instead of continuing best-effort
+    #[arg(long = "strict")]
+    pub strict: bool,
+
+    /// Read files re-included by a `.gitignore` negation (`!pattern`) as text, even if they look binary
+    #[arg(long = "respect-gitignore-reinclude-as-text")]
+    pub respect_gitignore_reinclude_as_text: bool,
+
+    /// Walk vendored dependency directories (vendor/, node_modules/, .venv/, ...) instead of pruning them
+    #[arg(long = "include-vendored")]
+    pub include_vendored: bool,
+
+    /// Ignore the user's global gitignore (core.excludesFile) and .git/info/exclude, keeping output reproducible across machines
+    #[arg(long = "no-global-gitignore")]
+    pub no_global_gitignore: bool,
+
+    /// Follow symlinked files and directories during the walk; each target is packed once
+    #[arg(long = "follow-symlinks")]
+    pub follow_symlinks: bool,
+
+    /// Only report errors; warnings (missing paths, dropped files, possible secrets) are silenced
+    #[arg(long = "quiet")]
+    pub quiet: bool,
+
+    /// Wrap each file's content in a Markdown code fence tagged with its language
+    #[arg(long = "fence-code")]
+    pub fence_code: bool,
+
+    /// Shell command run after each output file is written; `{file}` expands to its path
+    #[arg(long = "post-write-command")]
+    pub post_write_command: Option<String>,
+
+    /// Cut output streamed to stdout to this size (e.g. "50MB"), with a warning.
+    /// The output is still generated in full; only what is printed is cut.
+    #[arg(long = "truncate-stream-at")]
+    pub truncate_stream_at: Option<String>,
+
+    /// Show a progress bar on stderr while scanning git history and reading files
+    #[arg(long = "progress")]
+    pub progress: bool,
+
+    /// Compress output files (and streamed output) with "gzip", adding `.gz` to file names
+    #[arg(long = "compress")]
+    pub compress: Option<String>,
+
+    /// Memory-map files at least this large (e.g. "8MB") instead of copying them; needs the `mmap` feature
+    #[arg(long = "mmap-threshold")]
+    pub mmap_threshold: Option<String>,
+
+    /// Largest file to pack (e.g. "5MB"); bigger files are handled per `oversize_behavior`
+    #[arg(long = "max-file-size")]
+    pub max_file_size: Option<String>,
+
+    /// What to do with files over `max_file_size`: "skip" them with a warning or "truncate" them
+    #[arg(long = "oversize-behavior", default_value = "skip")]
+    pub oversize_behavior: String,
+
+    /// Shorten lines longer than this many characters (see `long_line_strategy`)
+    #[arg(long = "max-line-length")]
+    pub max_line_length: Option<usize>,
+
+    /// How to shorten long lines: "truncate" (with a marker) or "wrap"
+    #[arg(long = "long-line-strategy", default_value = "truncate")]
+    pub long_line_strategy: String,
+
+    /// In token mode, cut files longer than this many tokens (see `sample_large_files`)
+    #[arg(long = "max-file-tokens")]
+    pub max_file_tokens: Option<usize>,
+
+    /// Keep the first and last halves of `max_file_tokens` instead of only the start
+    #[arg(long = "sample-large-files")]
+    pub sample_large_files: bool,
+
+    /// Units for reported sizes: "bytes", "human" (KB/MB with one decimal) or "tokens"
+    #[arg(long = "size-display", default_value = "human")]
+    pub size_display: String,
+
+    /// Treat files as binary when more than this ratio of characters are invalid UTF-8
+    #[arg(long = "max-replacement-ratio", default_value = "0.3")]
+    pub max_replacement_ratio: f64,
+
+    /// Estimate tokens for this model and suggest a --tokens budget instead of writing output
+    #[arg(long = "plan")]
+    pub plan: Option<String>,
+
+    /// Tokens kept free for the prompt when planning against a model's context window
+    #[arg(long = "reserved-tokens", default_value = "4096")]
+    pub reserved_tokens: usize,
+
+    /// Other config files to load as a base for this one (resolved relative to it)
+    #[arg(skip)]
+    pub include: Vec<String>,
+
+    /// Exit with an error, without writing output, when the estimated token count exceeds this
+    #[arg(long = "fail-over-tokens")]
+    pub fail_over_tokens: Option<usize>,
+
+    /// Print the number of files that would be packed and exit, without reading them
+    #[arg(long = "count-only")]
+    pub count_only: bool,
+
+    /// List the files that would be packed with their priority and size, without writing output
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
+
+    /// Without input paths, pack the project root (nearest parent with .git, yek.toml or Cargo.toml) instead of the current directory
+    #[arg(long = "auto-root")]
+    pub auto_root: bool,
+
+    /// Skip the global user config (~/.config/yek/config.{toml,yaml,json})
+    #[arg(long = "no-global-config")]
+    pub no_global_config: bool,
+
+    /// Report which config file was used, or that defaults were applied
+    #[arg(long = "show-config-source")]
+    pub show_config_source: bool,
+
     /// True if we should stream output to stdout (computed)
+    #[arg(skip)]
     pub stream: bool,
 
     /// True if we should count tokens, not bytes (computed)
+    #[arg(skip)]
     pub token_mode: bool,
 
     /// Final resolved output file path (only used if not streaming)
+    #[arg(skip)]
     pub output_file_full_path: Option<String>,
 
-    /// Maximum depth to search for Git commit times
-    #[config_arg(accept_from = "config_only", default_value = "100")]
+    /// Config files that were loaded, project config first (computed)
+    #[arg(skip)]
+    pub config_files: Vec<String>,
+
+    /// Command line that started the run, for the preamble's {command} (computed)
+    #[arg(skip)]
+    pub command_line: Option<String>,
+
+    /// Problems found while loading the config, for the caller to report (computed)
+    #[arg(skip)]
+    pub warnings: Vec<String>,
+
+    /// Fields given on the command line, which config files and environment variables don't override (computed)
+    #[arg(skip)]
+    pub cli_fields: Vec<String>,
+
+    /// Maximum depth to search for Git commit times (0 reads no git history)
+    #[arg(skip)]
     pub max_git_depth: i32,
 }
 
@@ -121,6 +444,7 @@ impl Default for YekConfig {
             update: false,
             max_size: "10MB".to_string(),
             tokens: String::new(),
+            single_file: false,
             json: false,
             debug: false,
             line_numbers: false,
@@ -129,13 +453,85 @@ impl Default for YekConfig {
             output_template: Some(DEFAULT_OUTPUT_TEMPLATE.to_string()),
             ignore_patterns: Vec::new(),
             unignore_patterns: Vec::new(),
+            include_patterns: Vec::new(),
+            exclude_dir: Vec::new(),
+            exclude_languages: Vec::new(),
             priority_rules: Vec::new(),
+            priority_scores_file: None,
             binary_extensions: BINARY_FILE_EXTENSIONS
                 .iter()
                 .map(|s| s.to_string())
                 .collect(),
             git_boost_max: Some(100),
+            git_boost_strategy: None,
+            git_boost_weight: 0.5,
             category_weights: None,
+            formats: Vec::new(),
+            select: None,
+            select_count: None,
+            max_files: None,
+            max_total_size: None,
+            cache_dir: None,
+            preamble: None,
+            diff_output: false,
+            changed_with_diff: false,
+            blame: false,
+            blame_max_files: 100,
+            range: None,
+            git_diff_base: None,
+            git_diff_head: None,
+            since: None,
+            gitignore_at: None,
+            docs_first_per_dir: false,
+            dep_graph: false,
+            secret_scan: false,
+            redact_secrets: false,
+            redact_patterns: Vec::new(),
+            priority_order: "ascending".to_string(),
+            strip_comments: false,
+            no_sort: false,
+            manifests_first: false,
+            dedupe_boilerplate: false,
+            module_sections: false,
+            max_age: None,
+            git_since: None,
+            git_since_filter: false,
+            max_age_drop_unknown: false,
+            near_duplicate_threshold: None,
+            dedup_identical: false,
+            no_magic_detection: false,
+            binary_detection_bytes: None,
+            encoding: None,
+            fail_on_missing: false,
+            strict: false,
+            include_vendored: false,
+            no_global_gitignore: false,
+            follow_symlinks: false,
+            quiet: false,
+            fence_code: false,
+            respect_gitignore_reinclude_as_text: false,
+            post_write_command: None,
+            truncate_stream_at: None,
+            progress: false,
+            compress: None,
+            mmap_threshold: None,
+            max_file_size: None,
+            oversize_behavior: "skip".to_string(),
+            max_replacement_ratio: DEFAULT_MAX_REPLACEMENT_RATIO,
+            max_line_length: None,
+            long_line_strategy: "truncate".to_string(),
+            max_file_tokens: None,
+            sample_large_files: false,
+            size_display: "human".to_string(),
+            plan: None,
+            reserved_tokens: 4096,
+            include: Vec::new(),
+            fail_over_tokens: None,
+            count_only: false,
+            dry_run: false,
+            auto_root: false,
+            no_global_config: false,
+            show_config_source: false,
 
             // computed fields
             tree_header: false,
@@ -143,6 +539,10 @@ impl Default for YekConfig {
             stream: false,
             token_mode: false,
             output_file_full_path: None,
+            config_files: Vec::new(),
+            command_line: None,
+            warnings: Vec::new(),
+            cli_fields: Vec::new(),
             max_git_depth: 100,
         }
     }
@@ -157,6 +557,16 @@ impl YekConfig {
         }
     }
 
+    /// Where settings came from: the loaded config files, or "defaults" when
+    /// none was found
+    pub fn config_source(&self) -> String {
+        if self.config_files.is_empty() {
+            "defaults (no config file found)".to_string()
+        } else {
+            self.config_files.join(", ")
+        }
+    }
+
     /// Read input paths from stdin, filtering out empty lines and trimming whitespace
     fn read_input_paths_from_stdin(&self) -> Result<Vec<String>> {
         let stdin = io::stdin();
@@ -201,12 +611,43 @@ impl YekConfig {
         Ok(output_dir)
     }
 
+    /// Parse the command line and layer the config files beneath it: the
+    /// `--config-file`, or else every project config from the working
+    /// directory up, then the global config. Computed fields other than
+    /// `cli_fields` are left unset.
+    pub fn parse() -> Self {
+        let (cli, cli_fields) = parse_cli();
+        let mut set = cli_fields.clone();
+        let mut cfg = cli.config.with_defaults_except(&set);
+        cfg.cli_fields = cli_fields.into_iter().collect();
+        cfg.cli_fields.sort();
+
+        if let Some(config_path) = &cli.config_file {
+            cfg.apply_config_file(config_path, &mut set, &mut HashSet::new());
+        } else if !cli.no_config {
+            if let Ok(cwd) = std::env::current_dir() {
+                cfg.apply_project_configs(&cwd, &mut set);
+            }
+        }
+        if !cfg.no_global_config {
+            if let Some(global_path) = global_config_path() {
+                if !cfg
+                    .config_files
+                    .contains(&global_path.to_string_lossy().to_string())
+                {
+                    cfg.apply_config_file(&global_path, &mut set, &mut HashSet::new());
+                }
+            }
+        }
+        cfg
+    }
+
     /// Parse from CLI + config file, fill in computed fields, and validate.
     pub fn init_config() -> Self {
-        // 1) parse from CLI and optional config file:
-        let (mut cfg, config_path, _config_format) = YekConfig::parse_info();
-
-        cfg.apply_config_bool_overrides(config_path.as_deref());
+        // 1) parse the CLI, then layer the project configs (with their
+        // includes) and the global config underneath it. Fields given on
+        // the command line are never overridden.
+        let mut cfg = Self::parse();
 
         // Handle version flag
         if cfg.version {
@@ -231,6 +672,11 @@ impl YekConfig {
 
         cfg.stream = !std::io::stdout().is_terminal() && !force_tty;
 
+        if let Err(e) = apply_env_overrides(&mut cfg) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+
         // Handle default for output_template if not provided
         if cfg.output_template.is_none() {
             cfg.output_template = Some(DEFAULT_OUTPUT_TEMPLATE.to_string());
@@ -245,18 +691,18 @@ impl YekConfig {
                         if !stdin_paths.is_empty() {
                             cfg.input_paths = stdin_paths;
                         } else {
-                            // stdin was empty, default to current dir
-                            cfg.input_paths.push(".".to_string());
+                            // stdin was empty, default to current dir (or project root)
+                            cfg.push_default_input_path();
                         }
                     }
                     Err(e) => {
                         eprintln!("Warning: Failed to read from stdin: {}", e);
-                        cfg.input_paths.push(".".to_string());
+                        cfg.push_default_input_path();
                     }
                 }
             } else {
-                // No stdin input, default to current dir
-                cfg.input_paths.push(".".to_string());
+                // No stdin input, default to current dir (or project root)
+                cfg.push_default_input_path();
             }
         }
 
@@ -307,23 +753,180 @@ impl YekConfig {
         cfg
     }
 
-    fn apply_config_bool_overrides(&mut self, config_path: Option<&Path>) {
-        let Some(config_path) = config_path else {
+    /// Add the input path used when none is given: the current directory, or
+    /// with `auto_root` the project root containing it
+    fn push_default_input_path(&mut self) {
+        if self.auto_root {
+            if let Some(root) = std::env::current_dir()
+                .ok()
+                .and_then(|cwd| find_project_root(&cwd))
+            {
+                self.input_paths.push(root.to_string_lossy().to_string());
+                return;
+            }
+            self.warnings
+                .push("No project root found, using the current directory".to_string());
+        }
+        self.input_paths.push(".".to_string());
+    }
+
+    /// A copy with every field not named in `keep` reset to its default
+    fn with_defaults_except(self, keep: &HashSet<String>) -> Self {
+        let (Ok(serde_json::Value::Object(mut values)), Ok(serde_json::Value::Object(defaults))) = (
+            serde_json::to_value(&self),
+            serde_json::to_value(YekConfig::default()),
+        ) else {
+            return self;
+        };
+        for (key, default) in defaults {
+            if !keep.contains(&key) {
+                values.insert(key, default);
+            }
+        }
+        serde_json::from_value(serde_json::Value::Object(values)).unwrap_or(self)
+    }
+
+    /// Apply one config file, then the files in its `include` key beneath
+    /// it. Each field is taken from the first layer that sets it; fields in
+    /// `set` (from the CLI or higher-precedence files) are left alone. List
+    /// fields like `ignore_patterns` collect entries from every layer, base
+    /// layers first. Paths resolve relative to the including file and each
+    /// file is applied at most once, so include cycles are skipped.
+    fn apply_config_file(
+        &mut self,
+        path: &Path,
+        set: &mut HashSet<String>,
+        visited: &mut HashSet<PathBuf>,
+    ) {
+        let Ok(canonical) = path.canonicalize() else {
+            self.warnings
+                .push(format!("Config file '{}' does not exist", path.display()));
             return;
         };
+        visited.insert(canonical);
 
-        let Ok(settings) = ::config::Config::builder()
-            .add_source(::config::File::from(config_path).required(false))
-            .build()
-        else {
+        let layer = match read_config_layer(path) {
+            Ok(layer) => layer,
+            Err(e) => {
+                self.warnings
+                    .push(format!("Failed to read config '{}': {}", path.display(), e));
+                return;
+            }
+        };
+        self.apply_config_layer(&layer, path, set);
+        self.config_files.push(path.to_string_lossy().to_string());
+
+        let includes: Vec<String> = layer
+            .get("include")
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default();
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+
+        // Later includes take precedence over earlier ones, so apply them first
+        for include in includes.iter().rev() {
+            let include_path = base_dir.join(include);
+            let Ok(canonical) = include_path.canonicalize() else {
+                self.warnings.push(format!(
+                    "Included config '{}' does not exist",
+                    include_path.display()
+                ));
+                continue;
+            };
+            if visited.contains(&canonical) {
+                self.warnings.push(format!(
+                    "Skipping recursive config include '{}'",
+                    include_path.display()
+                ));
+                continue;
+            }
+            self.apply_config_file(&include_path, set, visited);
+        }
+    }
+
+    /// Apply every `yek.*` config from `start` up to the filesystem root, the
+    /// way nested `.gitignore` files layer: the nearest file wins for scalars
+    /// and bools, and lists collect entries from all of them.
+    fn apply_project_configs(&mut self, start: &Path, set: &mut HashSet<String>) {
+        let start = start.canonicalize().unwrap_or_else(|_| start.to_path_buf());
+        for dir in start.ancestors() {
+            let mut found = CONFIG_FILE_EXTENSIONS
+                .iter()
+                .map(|ext| dir.join(format!("yek.{}", ext)))
+                .filter(|path| path.is_file());
+            let Some(path) = found.next() else {
+                continue;
+            };
+            if let Some(other) = found.next() {
+                self.warnings.push(format!(
+                    "Multiple config files in '{}'; using '{}' and ignoring '{}'",
+                    dir.display(),
+                    path.display(),
+                    other.display()
+                ));
+            }
+            self.apply_config_file(&path, set, &mut HashSet::new());
+        }
+    }
+
+    /// Fill the fields `layer` sets that aren't in `set` yet, and mark them
+    /// set. Fields in `MERGED_LIST_FIELDS` get the layer's entries prepended
+    /// instead, skipping ones already present. Values of the wrong type are
+    /// skipped with a warning.
+    fn apply_config_layer(
+        &mut self,
+        layer: &ConfigLayer,
+        source: &Path,
+        set: &mut HashSet<String>,
+    ) {
+        let Ok(serde_json::Value::Object(mut values)) = serde_json::to_value(&*self) else {
             return;
         };
+        let mut warnings = Vec::new();
+        for (key, value) in layer {
+            if NON_CONFIG_FIELDS.contains(&key.as_str()) || !values.contains_key(key) {
+                continue;
+            }
+            let merged_list = MERGED_LIST_FIELDS.contains(&key.as_str());
+            let new_value = match (merged_list, values.get(key), value) {
+                (
+                    true,
+                    Some(serde_json::Value::Array(existing)),
+                    serde_json::Value::Array(added),
+                ) => {
+                    let mut merged: Vec<_> = added
+                        .iter()
+                        .filter(|entry| !existing.contains(entry))
+                        .cloned()
+                        .collect();
+                    merged.extend(existing.iter().cloned());
+                    serde_json::Value::Array(merged)
+                }
+                _ if set.contains(key) => continue,
+                _ => value.clone(),
+            };
 
-        self.json |= config_bool(&settings, "json", "json");
-        self.debug |= config_bool(&settings, "debug", "debug");
-        self.line_numbers |= config_bool(&settings, "line_numbers", "line-numbers");
-        self.tree_header |= config_bool(&settings, "tree_header", "tree-header");
-        self.tree_only |= config_bool(&settings, "tree_only", "tree-only");
+            let previous = values.insert(key.clone(), new_value);
+            match serde_json::from_value::<YekConfig>(serde_json::Value::Object(values.clone())) {
+                Ok(cfg) => {
+                    *self = cfg;
+                    if !merged_list {
+                        set.insert(key.clone());
+                    }
+                }
+                Err(e) => {
+                    warnings.push(format!(
+                        "Ignoring '{}' in '{}': {}",
+                        key,
+                        source.display(),
+                        e
+                    ));
+                    if let Some(previous) = previous {
+                        values.insert(key.clone(), previous);
+                    }
+                }
+            }
+        }
+        self.warnings.extend(warnings);
     }
 
     /// Compute a quick checksum for the input paths (files and directories).
@@ -401,6 +1004,22 @@ impl YekConfig {
             return Err(anyhow!("max_size: cannot be 0"));
         }
 
+        if self.quiet && self.debug {
+            return Err(anyhow!("quiet: cannot be combined with debug"));
+        }
+
+        if self.max_files == Some(0) {
+            return Err(anyhow!("max_files: cannot be 0"));
+        }
+
+        if self.max_git_depth < 0 {
+            return Err(anyhow!("max_git_depth: cannot be negative"));
+        }
+
+        if crate::total_size_limit(self)? == Some(0) {
+            return Err(anyhow!("max_total_size: cannot be 0"));
+        }
+
         if !self.token_mode {
             ByteSize::from_str(&self.max_size)
                 .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?;
@@ -438,10 +1057,33 @@ impl YekConfig {
 
         // Validate ignore patterns
         for pattern in &self.ignore_patterns {
+            if let Some(bracket) = unclosed_glob_group(pattern) {
+                return Err(anyhow!(
+                    "ignore_patterns: Invalid pattern '{}': unclosed '{}'",
+                    pattern,
+                    bracket
+                ));
+            }
             glob::Pattern::new(pattern)
                 .map_err(|e| anyhow!("ignore_patterns: Invalid pattern '{}': {}", pattern, e))?;
         }
 
+        // Include patterns use gitignore syntax, like the walk's ignore rules
+        for pattern in &self.include_patterns {
+            ignore::gitignore::GitignoreBuilder::new("")
+                .add_line(None, pattern)
+                .map_err(|e| anyhow!("include_patterns: Invalid pattern '{}': {}", pattern, e))?;
+        }
+
+        if let Some(max_boost) = self.git_boost_max {
+            if !(0..=1000).contains(&max_boost) {
+                return Err(anyhow!(
+                    "git_boost_max: {} must be between 0 and 1000",
+                    max_boost
+                ));
+            }
+        }
+
         // Validate priority rules
         for rule in &self.priority_rules {
             if rule.score < 0 || rule.score > 1000 {
@@ -455,6 +1097,30 @@ impl YekConfig {
             })?;
         }
 
+        for pattern in &self.redact_patterns {
+            regex::Regex::new(pattern)
+                .map_err(|e| anyhow!("redact_patterns: Invalid pattern '{}': {}", pattern, e))?;
+        }
+
+        if let Some(range) = &self.range {
+            if !range.contains("..") {
+                return Err(anyhow!(
+                    "range: Expected a commit range like 'A..B', got '{}'",
+                    range
+                ));
+            }
+        }
+
+        if self.git_diff_head.is_some() && self.git_diff_base.is_none() {
+            return Err(anyhow!("git_diff_head: requires git_diff_base"));
+        }
+
+        if self.diff_output && self.changed_with_diff {
+            return Err(anyhow!(
+                "diff_output and changed_with_diff cannot both be enabled"
+            ));
+        }
+
         // Validate tree options are mutually exclusive
         if self.tree_header && self.tree_only {
             return Err(anyhow!("tree_header and tree_only cannot both be enabled"));
@@ -469,6 +1135,179 @@ impl YekConfig {
             return Err(anyhow!("JSON output not supported in tree-only mode"));
         }
 
+        if let Some(limit) = &self.truncate_stream_at {
+            ByteSize::from_str(limit)
+                .map_err(|e| anyhow!("truncate_stream_at: Invalid size format: {}", e))?;
+        }
+
+        if let Some(compress) = &self.compress {
+            if !COMPRESSIONS.contains(&compress.as_str()) {
+                return Err(anyhow!(
+                    "compress: must be one of {}, got '{}'",
+                    COMPRESSIONS.join(", "),
+                    compress
+                ));
+            }
+        }
+
+        if let Some(threshold) = &self.mmap_threshold {
+            ByteSize::from_str(threshold)
+                .map_err(|e| anyhow!("mmap_threshold: Invalid size format: {}", e))?;
+        }
+
+        if let Some(size) = &self.max_file_size {
+            let size = ByteSize::from_str(size)
+                .map_err(|e| anyhow!("max_file_size: Invalid size format: {}", e))?;
+            if size.as_u64() == 0 {
+                return Err(anyhow!("max_file_size: cannot be 0"));
+            }
+        }
+        if !OVERSIZE_BEHAVIORS.contains(&self.oversize_behavior.as_str()) {
+            return Err(anyhow!(
+                "oversize_behavior: must be one of {}, got '{}'",
+                OVERSIZE_BEHAVIORS.join(", "),
+                self.oversize_behavior
+            ));
+        }
+
+        if self.max_line_length == Some(0) {
+            return Err(anyhow!("max_line_length: cannot be 0"));
+        }
+        if self.long_line_strategy != "truncate" && self.long_line_strategy != "wrap" {
+            return Err(anyhow!(
+                "long_line_strategy: must be 'truncate' or 'wrap', got '{}'",
+                self.long_line_strategy
+            ));
+        }
+
+        if self.max_file_tokens == Some(0) {
+            return Err(anyhow!("max_file_tokens: cannot be 0"));
+        }
+
+        if !PRIORITY_ORDERS.contains(&self.priority_order.as_str()) {
+            return Err(anyhow!(
+                "priority_order: must be one of {}, got '{}'",
+                PRIORITY_ORDERS.join(", "),
+                self.priority_order
+            ));
+        }
+
+        if !SIZE_DISPLAYS.contains(&self.size_display.as_str()) {
+            return Err(anyhow!(
+                "size_display: must be one of {}, got '{}'",
+                SIZE_DISPLAYS.join(", "),
+                self.size_display
+            ));
+        }
+
+        if let Some(strategy) = &self.git_boost_strategy {
+            if !GIT_BOOST_STRATEGIES.contains(&strategy.as_str()) {
+                return Err(anyhow!(
+                    "git_boost_strategy: must be one of {}, got '{}'",
+                    GIT_BOOST_STRATEGIES.join(", "),
+                    strategy
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.git_boost_weight) {
+            return Err(anyhow!(
+                "git_boost_weight: must be between 0 and 1, got {}",
+                self.git_boost_weight
+            ));
+        }
+
+        if self.binary_detection_bytes == Some(0) {
+            return Err(anyhow!("binary_detection_bytes: cannot be 0"));
+        }
+
+        if let Some(label) = &self.encoding {
+            if encoding_rs::Encoding::for_label(label.as_bytes()).is_none() {
+                return Err(anyhow!("encoding: unknown encoding '{}'", label));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.max_replacement_ratio) {
+            return Err(anyhow!(
+                "max_replacement_ratio: must be between 0 and 1, got {}",
+                self.max_replacement_ratio
+            ));
+        }
+
+        // Validate planner model
+        if let Some(model) = &self.plan {
+            let Some((_, window)) = MODEL_CONTEXT_WINDOWS.iter().find(|(name, _)| name == model)
+            else {
+                let known: Vec<&str> = MODEL_CONTEXT_WINDOWS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect();
+                return Err(anyhow!(
+                    "plan: Unknown model '{}' (expected one of: {})",
+                    model,
+                    known.join(", ")
+                ));
+            };
+            if self.reserved_tokens >= *window {
+                return Err(anyhow!(
+                    "reserved_tokens: {} leaves no room in {}'s {} token context window",
+                    self.reserved_tokens,
+                    model,
+                    window
+                ));
+            }
+        }
+
+        // Blame tags each line as committed, so it can't describe stripped content
+        if self.strip_comments && self.blame {
+            return Err(anyhow!("strip_comments: cannot be combined with blame"));
+        }
+
+        if self.git_since_filter && self.git_since.is_none() {
+            return Err(anyhow!("git_since_filter: requires git_since to be set"));
+        }
+
+        // Validate maximum file age
+        if let Some(max_age) = &self.max_age {
+            crate::parse_age(max_age)?;
+        }
+
+        // Validate near-duplicate threshold
+        if let Some(threshold) = self.near_duplicate_threshold {
+            if !(threshold > 0.0 && threshold <= 1.0) {
+                return Err(anyhow!(
+                    "near_duplicate_threshold: must be greater than 0 and at most 1, got {}",
+                    threshold
+                ));
+            }
+        }
+
+        // Validate size-based selection
+        if let Some(select) = &self.select {
+            if select != "largest" && select != "smallest" {
+                return Err(anyhow!(
+                    "select: must be either 'largest' or 'smallest', got '{}'",
+                    select
+                ));
+            }
+            match self.select_count {
+                None => return Err(anyhow!("select_count: must be provided when select is set")),
+                Some(0) => return Err(anyhow!("select_count: cannot be 0")),
+                Some(_) => {}
+            }
+        }
+
+        // Validate requested output formats
+        for format in &self.formats {
+            if !OUTPUT_FORMATS.contains(&format.as_str()) {
+                return Err(anyhow!(
+                    "formats: Unknown output format '{}' (expected one of: {})",
+                    format,
+                    OUTPUT_FORMATS.join(", ")
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -699,11 +1538,345 @@ impl YekConfig {
     }
 }
 
-fn config_bool(settings: &::config::Config, snake_case_key: &str, kebab_case_key: &str) -> bool {
-    settings
-        .get_bool(snake_case_key)
-        .or_else(|_| settings.get_bool(kebab_case_key))
-        .unwrap_or(false)
+/// Chainable way to build a validated [`YekConfig`] when embedding yek as a
+/// library. Starts from the same defaults as [`YekConfig::default`].
+///
+/// ```no_run
+/// use yek::config::YekConfig;
+///
+/// let config = YekConfig::builder()
+///     .input_path("src")
+///     .max_size("128K")
+///     .add_ignore("*.log")
+///     .add_priority_rule("src/.*\\.rs", 100)
+///     .build()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct YekConfigBuilder {
+    config: YekConfig,
+}
+
+impl YekConfig {
+    pub fn builder() -> YekConfigBuilder {
+        YekConfigBuilder::default()
+    }
+}
+
+impl YekConfigBuilder {
+    pub fn input_path(mut self, path: impl Into<String>) -> Self {
+        self.config.input_paths.push(path.into());
+        self
+    }
+
+    pub fn input_paths<I, S>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.config
+            .input_paths
+            .extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// Max size per chunk, e.g. "10MB" (or "128K" tokens in token mode)
+    pub fn max_size(mut self, size: impl Into<String>) -> Self {
+        self.config.max_size = size.into();
+        self
+    }
+
+    /// Count chunk sizes in tokens. Unless [`tokens`](Self::tokens) is set,
+    /// `max_size` is taken as the token limit.
+    pub fn token_mode(mut self, enabled: bool) -> Self {
+        self.config.token_mode = enabled;
+        self
+    }
+
+    /// Token limit per chunk (e.g. "128k"); implies token mode
+    pub fn tokens(mut self, limit: impl Into<String>) -> Self {
+        self.config.tokens = limit.into();
+        self.config.token_mode = true;
+        self
+    }
+
+    pub fn add_ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.config.ignore_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn add_unignore(mut self, pattern: impl Into<String>) -> Self {
+        self.config.unignore_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn add_include(mut self, pattern: impl Into<String>) -> Self {
+        self.config.include_patterns.push(pattern.into());
+        self
+    }
+
+    pub fn add_priority_rule(mut self, pattern: impl Into<String>, score: i32) -> Self {
+        self.config.priority_rules.push(PriorityRule {
+            pattern: pattern.into(),
+            score,
+        });
+        self
+    }
+
+    pub fn output_dir(mut self, dir: impl Into<String>) -> Self {
+        self.config.output_dir = Some(dir.into());
+        self
+    }
+
+    pub fn output_template(mut self, template: impl Into<String>) -> Self {
+        self.config.output_template = Some(template.into());
+        self
+    }
+
+    pub fn json(mut self, enabled: bool) -> Self {
+        self.config.json = enabled;
+        self
+    }
+
+    pub fn line_numbers(mut self, enabled: bool) -> Self {
+        self.config.line_numbers = enabled;
+        self
+    }
+
+    pub fn tree_header(mut self, enabled: bool) -> Self {
+        self.config.tree_header = enabled;
+        self
+    }
+
+    /// Return output instead of writing it to `output_dir`
+    pub fn stream(mut self, enabled: bool) -> Self {
+        self.config.stream = enabled;
+        self
+    }
+
+    /// Validate and return the config
+    pub fn build(mut self) -> Result<YekConfig> {
+        if self.config.token_mode && self.config.tokens.is_empty() {
+            self.config.tokens = self.config.max_size.clone();
+        }
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Settings from one config file, keyed by field name. Keys may be written
+/// in kebab-case (`max-size`) or snake_case (`max_size`).
+type ConfigLayer = serde_json::Map<String, serde_json::Value>;
+
+/// List fields that collect entries from every config layer instead of
+/// taking the nearest file's value
+const MERGED_LIST_FIELDS: &[&str] = &[
+    "ignore_patterns",
+    "unignore_patterns",
+    "include_patterns",
+    "exclude_languages",
+    "binary_extensions",
+    "redact_patterns",
+    "priority_rules",
+];
+
+/// Fields a config file can't set: command-only flags and computed values
+const NON_CONFIG_FIELDS: &[&str] = &[
+    "input_paths",
+    "version",
+    "update",
+    "count_only",
+    "dry_run",
+    "no_global_config",
+    "show_config_source",
+    "stream",
+    "token_mode",
+    "output_file_full_path",
+    "config_files",
+    "command_line",
+    "warnings",
+    "cli_fields",
+];
+
+/// Read a TOML, YAML or JSON config file (by extension) into a layer. A
+/// leading UTF-8 byte order mark, as some Windows editors write, is skipped.
+fn read_config_layer(path: &Path) -> Result<ConfigLayer> {
+    let text = fs::read_to_string(path)?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let format = match path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+        .as_deref()
+    {
+        Some("yaml" | "yml") => ::config::FileFormat::Yaml,
+        Some("json") => ::config::FileFormat::Json,
+        _ => ::config::FileFormat::Toml,
+    };
+    let layer: ConfigLayer = ::config::Config::builder()
+        .add_source(::config::File::from_str(text, format))
+        .build()?
+        .try_deserialize()?;
+    Ok(layer
+        .into_iter()
+        .map(|(key, value)| (key.replace('-', "_"), value))
+        .collect())
+}
+
+/// Command-line arguments: every config option plus the choice of config file
+#[derive(clap::Parser)]
+#[command(name = "yek", about)]
+struct Cli {
+    /// Do not use a config file
+    #[arg(long = "no-config")]
+    no_config: bool,
+
+    /// Path to the config file
+    #[arg(long = "config-file")]
+    config_file: Option<PathBuf>,
+
+    #[command(flatten)]
+    config: YekConfig,
+}
+
+/// Parse the command line. Config files are layered by `YekConfig::parse`, not
+/// here. Also returns the names of the fields given on the command line, as
+/// opposed to left at their defaults.
+fn parse_cli() -> (Cli, HashSet<String>) {
+    let command = Cli::command();
+    let matches = command.clone().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let given = command
+        .get_arguments()
+        .map(|arg| arg.get_id().as_str())
+        .filter(|id| matches.value_source(id) == Some(ValueSource::CommandLine))
+        .map(str::to_string)
+        .collect();
+    (cli, given)
+}
+
+/// The bracket (`{` or `[`) a glob pattern leaves unclosed, if any. Such
+/// patterns would otherwise be taken literally and quietly match nothing.
+fn unclosed_glob_group(pattern: &str) -> Option<char> {
+    let bytes = pattern.as_bytes();
+    let mut open_braces = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'[' => {
+                // A `]` right after `[` (or `[!`) is a member, not the end of the class
+                let mut start = i + 1;
+                if matches!(bytes.get(start), Some(b'!' | b'^')) {
+                    start += 1;
+                }
+                let close = bytes
+                    .get(start + 1..)
+                    .and_then(|rest| rest.iter().position(|&b| b == b']'));
+                match close {
+                    Some(offset) => i = start + 1 + offset,
+                    None => return Some('['),
+                }
+            }
+            b'{' => open_braces += 1,
+            b'}' => open_braces = open_braces.saturating_sub(1),
+            _ => {}
+        }
+        i += 1;
+    }
+    (open_braces > 0).then_some('{')
+}
+
+/// Config file extensions yek looks for, in order of preference
+const CONFIG_FILE_EXTENSIONS: &[&str] = &["toml", "yaml", "yml", "json"];
+
+/// Nearest ancestor of `start` (itself included) holding a project root
+/// marker. Markers are tried in order, so a `.git` further up wins over a
+/// nested crate's `Cargo.toml`.
+pub fn find_project_root(start: &Path) -> Option<PathBuf> {
+    PROJECT_ROOT_MARKERS.iter().find_map(|marker| {
+        start
+            .ancestors()
+            .find(|dir| dir.join(marker).exists())
+            .map(Path::to_path_buf)
+    })
+}
+
+/// Location of the global user config, e.g. `~/.config/yek/config.toml`.
+/// Honors `XDG_CONFIG_HOME` when set.
+pub fn global_config_path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    CONFIG_FILE_EXTENSIONS
+        .iter()
+        .map(|ext| base.join("yek").join(format!("config.{}", ext)))
+        .find(|path| path.is_file())
+}
+
+/// Merge every `yek.*` config from `start` up to the filesystem root, the
+/// way nested `.gitignore` files layer. Deeper files win for scalars, list
+/// fields like `ignore_patterns` and `priority_rules` collect entries from
+/// all of them (ancestors first), and each file's includes sit right
+/// beneath it. This is what the CLI does from the working directory.
+/// Problems such as unreadable files or invalid values are skipped and
+/// listed in `warnings`.
+pub fn load_merged_config(start: &Path) -> YekConfig {
+    let mut cfg = YekConfig::default();
+    cfg.apply_project_configs(start, &mut HashSet::new());
+    cfg
+}
+
+/// Override loaded settings from `YEK_MAX_SIZE`, `YEK_TOKEN_MODE`,
+/// `YEK_OUTPUT_DIR` and `YEK_STREAM`, so CI can tweak a run without editing
+/// the config file. Precedence is flags, then environment, then config
+/// files, then defaults: a variable is skipped when its field is in
+/// `cli_fields`. Empty variables are ignored.
+pub fn apply_env_overrides(cfg: &mut YekConfig) -> Result<()> {
+    apply_env_vars(cfg, |key| std::env::var(key).ok())
+}
+
+fn apply_env_vars(cfg: &mut YekConfig, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+    let cli_fields = cfg.cli_fields.clone();
+    let var = |key: &str, field: &str| {
+        lookup(key)
+            .filter(|value| !value.trim().is_empty())
+            .filter(|_| !cli_fields.iter().any(|given| given == field))
+    };
+
+    if let Some(max_size) = var("YEK_MAX_SIZE", "max_size") {
+        ByteSize::from_str(max_size.trim())
+            .map_err(|e| anyhow!("YEK_MAX_SIZE: Invalid size format: {}", e))?;
+        cfg.max_size = max_size.trim().to_string();
+    }
+    if let Some(token_mode) = var("YEK_TOKEN_MODE", "tokens") {
+        cfg.token_mode = parse_env_bool("YEK_TOKEN_MODE", &token_mode)?;
+        // Like the builder, an unset token limit falls back to max_size
+        if cfg.token_mode && cfg.tokens.is_empty() {
+            cfg.tokens = cfg.max_size.clone();
+        }
+    }
+    if let Some(output_dir) = var("YEK_OUTPUT_DIR", "output_dir") {
+        cfg.output_dir = Some(output_dir);
+    }
+    if let Some(stream) = var("YEK_STREAM", "stream") {
+        cfg.stream = parse_env_bool("YEK_STREAM", &stream)?;
+    }
+    Ok(())
+}
+
+fn parse_env_bool(key: &str, value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(anyhow!(
+            "{}: expected 1/true or 0/false, got '{}'",
+            key,
+            value
+        )),
+    }
 }
 
 #[cfg(test)]
@@ -721,7 +1894,7 @@ mod bool_config_tests {
         .unwrap();
 
         let mut cfg = YekConfig::default();
-        cfg.apply_config_bool_overrides(Some(&path));
+        cfg.apply_config_file(&path, &mut HashSet::new(), &mut HashSet::new());
 
         assert!(cfg.json && cfg.debug && cfg.line_numbers && cfg.tree_header && cfg.tree_only);
     }
@@ -733,9 +1906,297 @@ mod bool_config_tests {
         fs::write(&path, "debug: [").unwrap();
 
         let mut cfg = YekConfig::default();
-        cfg.apply_config_bool_overrides(None);
-        cfg.apply_config_bool_overrides(Some(&path));
+        cfg.apply_config_file(
+            &dir.path().join("missing.yaml"),
+            &mut HashSet::new(),
+            &mut HashSet::new(),
+        );
+        cfg.apply_config_file(&path, &mut HashSet::new(), &mut HashSet::new());
 
         assert!(!cfg.json && !cfg.debug && !cfg.line_numbers && !cfg.tree_header && !cfg.tree_only);
     }
 }
+
+#[cfg(test)]
+mod global_config_tests {
+    use super::*;
+
+    #[test]
+    fn global_setting_applies_when_project_omits_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "max_size = \"2MB\"\nignore_patterns = [\"*.log\"]\n").unwrap();
+
+        let mut cfg = YekConfig {
+            ignore_patterns: vec!["target/**".to_string()],
+            ..YekConfig::default()
+        };
+        cfg.apply_config_file(&path, &mut HashSet::new(), &mut HashSet::new());
+
+        assert_eq!(cfg.max_size, "2MB");
+        assert_eq!(cfg.ignore_patterns, vec!["*.log", "target/**"]);
+    }
+
+    #[test]
+    fn project_setting_overrides_global() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "max_size = \"2MB\"\ntokens = \"128k\"\n").unwrap();
+
+        let mut cfg = YekConfig {
+            max_size: "5MB".to_string(),
+            tokens: "64k".to_string(),
+            ..YekConfig::default()
+        };
+        let mut set = HashSet::from(["max_size".to_string(), "tokens".to_string()]);
+        cfg.apply_config_file(&path, &mut set, &mut HashSet::new());
+
+        assert_eq!(cfg.max_size, "5MB");
+        assert_eq!(cfg.tokens, "64k");
+    }
+
+    #[test]
+    fn global_fills_any_field_the_project_leaves_unset() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = dir.path().join("yek.toml");
+        fs::write(&project, "max_size = \"10MB\"\n").unwrap();
+        let global = dir.path().join("config.toml");
+        fs::write(
+            &global,
+            "max_size = \"2MB\"\npriority_order = \"descending\"\nmax_files = 50\nstrip-comments = true\n",
+        )
+        .unwrap();
+
+        let mut cfg = YekConfig::default();
+        let mut set = HashSet::new();
+        cfg.apply_config_file(&project, &mut set, &mut HashSet::new());
+        cfg.apply_config_file(&global, &mut set, &mut HashSet::new());
+
+        // The project's explicit max_size wins even though it equals the default
+        assert_eq!(cfg.max_size, "10MB");
+        assert_eq!(cfg.priority_order, "descending");
+        assert_eq!(cfg.max_files, Some(50));
+        assert!(cfg.strip_comments);
+    }
+
+    #[test]
+    fn zero_max_git_depth_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("yek.toml");
+        fs::write(&path, "max_git_depth = 0\n").unwrap();
+
+        let mut cfg = YekConfig::default();
+        cfg.apply_config_file(&path, &mut HashSet::new(), &mut HashSet::new());
+
+        assert_eq!(cfg.max_git_depth, 0);
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_values_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(
+            &path,
+            "max_files = \"many\"\npriority_order = \"descending\"\n",
+        )
+        .unwrap();
+
+        let mut cfg = YekConfig::default();
+        cfg.apply_config_file(&path, &mut HashSet::new(), &mut HashSet::new());
+
+        assert_eq!(cfg.max_files, None);
+        assert_eq!(cfg.priority_order, "descending");
+    }
+}
+
+#[cfg(test)]
+mod merged_config_tests {
+    use super::*;
+
+    #[test]
+    fn deeper_configs_override_scalars_and_append_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("packages/app");
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(
+            dir.path().join("yek.toml"),
+            "max_size = \"2MB\"\ntokens = \"100k\"\nignore_patterns = [\"*.log\"]\n",
+        )
+        .unwrap();
+        fs::write(
+            package_dir.join("yek.toml"),
+            "max_size = \"1MB\"\nignore_patterns = [\"*.snap\"]\njson = true\n",
+        )
+        .unwrap();
+
+        let cfg = load_merged_config(&package_dir);
+
+        assert_eq!(cfg.max_size, "1MB");
+        assert_eq!(cfg.tokens, "100k");
+        assert_eq!(cfg.ignore_patterns, vec!["*.log", "*.snap"]);
+        assert!(cfg.json);
+        assert_eq!(cfg.config_files.len(), 2);
+    }
+
+    #[test]
+    fn nearest_config_wins_for_bools() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("packages/app");
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(
+            dir.path().join("yek.toml"),
+            "json = true
+quiet = true
+",
+        )
+        .unwrap();
+        fs::write(
+            package_dir.join("yek.toml"),
+            "json = false
+",
+        )
+        .unwrap();
+
+        let cfg = load_merged_config(&package_dir);
+
+        assert!(!cfg.json);
+        assert!(cfg.quiet);
+    }
+}
+
+#[cfg(test)]
+mod env_override_tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn apply(cfg: &mut YekConfig, cli_fields: &[&str], vars: &[(&str, &str)]) -> Result<()> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        cfg.cli_fields = cli_fields.iter().map(|field| field.to_string()).collect();
+        apply_env_vars(cfg, |key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn env_overrides_loaded_settings() {
+        let mut cfg = YekConfig {
+            max_size: "1MB".to_string(),
+            output_dir: Some("from-config".to_string()),
+            ..YekConfig::default()
+        };
+        apply(
+            &mut cfg,
+            &[],
+            &[
+                ("YEK_MAX_SIZE", "256KB"),
+                ("YEK_TOKEN_MODE", "true"),
+                ("YEK_OUTPUT_DIR", "from-env"),
+                ("YEK_STREAM", "1"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(cfg.max_size, "256KB");
+        assert!(cfg.token_mode);
+        assert_eq!(cfg.tokens, "256KB");
+        assert_eq!(cfg.output_dir.as_deref(), Some("from-env"));
+        assert!(cfg.stream);
+
+        apply(
+            &mut cfg,
+            &[],
+            &[("YEK_TOKEN_MODE", "0"), ("YEK_STREAM", "")],
+        )
+        .unwrap();
+        assert!(!cfg.token_mode);
+        assert!(cfg.stream);
+    }
+
+    #[test]
+    fn cli_flags_beat_env() {
+        let mut cfg = YekConfig {
+            max_size: "1MB".to_string(),
+            output_dir: Some("from-cli".to_string()),
+            ..YekConfig::default()
+        };
+        apply(
+            &mut cfg,
+            &["max_size", "output_dir"],
+            &[("YEK_MAX_SIZE", "256KB"), ("YEK_OUTPUT_DIR", "from-env")],
+        )
+        .unwrap();
+
+        assert_eq!(cfg.max_size, "1MB");
+        assert_eq!(cfg.output_dir.as_deref(), Some("from-cli"));
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let mut cfg = YekConfig::default();
+        let err = apply(&mut cfg, &[], &[("YEK_MAX_SIZE", "lots")]).unwrap_err();
+        assert!(err.to_string().starts_with("YEK_MAX_SIZE:"));
+        let err = apply(&mut cfg, &[], &[("YEK_STREAM", "maybe")]).unwrap_err();
+        assert!(err.to_string().starts_with("YEK_STREAM:"));
+    }
+}
+
+#[cfg(test)]
+mod include_config_tests {
+    use super::*;
+
+    #[test]
+    fn included_ignore_patterns_merge_into_child() {
+        let dir = tempfile::tempdir().unwrap();
+        let package_dir = dir.path().join("packages/app");
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(
+            dir.path().join("shared-yek.toml"),
+            "max_size = \"1MB\"\nignore_patterns = [\"fixtures/**\"]\ninclude = [\"packages/app/yek.toml\"]\n",
+        )
+        .unwrap();
+        let child = package_dir.join("yek.toml");
+        fs::write(
+            &child,
+            "include = [\"../../shared-yek.toml\"]\nignore_patterns = [\"*.snap\"]\n",
+        )
+        .unwrap();
+
+        let mut cfg = YekConfig::default();
+        cfg.apply_config_file(&child, &mut HashSet::new(), &mut HashSet::new());
+
+        assert_eq!(cfg.ignore_patterns, vec!["fixtures/**", "*.snap"]);
+        assert_eq!(cfg.max_size, "1MB");
+    }
+
+    #[test]
+    fn included_scalars_fill_only_what_the_child_leaves_unset() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("base.yaml"),
+            "max_files: 20
+reserved_tokens: 512
+priority_order: descending
+line_numbers: true
+",
+        )
+        .unwrap();
+        let child = dir.path().join("yek.yaml");
+        fs::write(
+            &child,
+            "include: [base.yaml]
+max_files: 5
+",
+        )
+        .unwrap();
+
+        let mut cfg = YekConfig::default();
+        cfg.apply_config_file(&child, &mut HashSet::new(), &mut HashSet::new());
+
+        assert_eq!(cfg.max_files, Some(5));
+        assert_eq!(cfg.reserved_tokens, 512);
+        assert_eq!(cfg.priority_order, "descending");
+        assert!(cfg.line_numbers);
+    }
+}
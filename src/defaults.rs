@@ -124,6 +124,7 @@ pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
     "pnpm-lock.yaml",
     "yek.toml",
     "yek.yaml",
+    "yek.yml",
     "yek.json",
     "package-lock.json",
     "yarn.lock",
@@ -158,3 +159,120 @@ pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[
 ];
 
 pub const DEFAULT_OUTPUT_TEMPLATE: &str = ">>>> FILE_PATH\nFILE_CONTENT";
+
+/// Template used for the "markdown" output format
+pub const MARKDOWN_OUTPUT_TEMPLATE: &str = "## FILE_PATH\n\nFILE_FENCE\nFILE_CONTENT\nFILE_FENCE\n";
+
+/// Output formats that can be requested via `formats`
+pub const OUTPUT_FORMATS: &[&str] = &["text", "markdown", "json", "embeddings"];
+
+/// Directions files can be ordered by priority (see `priority_order`)
+pub const PRIORITY_ORDERS: &[&str] = &["ascending", "descending"];
+
+/// Units sizes are reported in (see `size_display`)
+pub const SIZE_DISPLAYS: &[&str] = &["bytes", "human", "tokens"];
+
+/// What happens to files over `max_file_size`
+pub const OVERSIZE_BEHAVIORS: &[&str] = &["skip", "truncate"];
+
+/// Compression formats for written output (see `compress`)
+pub const COMPRESSIONS: &[&str] = &["gzip"];
+
+/// Ways the git recency boost can combine with rule scores (see `git_boost_strategy`)
+pub const GIT_BOOST_STRATEGIES: &[&str] = &["add", "max", "weighted"];
+
+/// Strategy used when `git_boost_strategy` is unset
+pub const DEFAULT_GIT_BOOST_STRATEGY: &str = "add";
+
+/// Files whose lossy UTF-8 decoding yields more than this fraction of U+FFFD
+/// replacement characters are treated as binary
+pub const DEFAULT_MAX_REPLACEMENT_RATIO: f64 = 0.3;
+
+/// Bytes sampled from the start of a file to decide whether it is binary
+pub const DEFAULT_BINARY_DETECTION_BYTES: usize = 8192;
+
+/// Samples in which more than this fraction of bytes are control characters
+/// (other than tab, line breaks, form feed and escape) are treated as binary
+pub const BINARY_CONTROL_RATIO: f64 = 0.3;
+
+/// Context window sizes (in tokens) of models known to the `plan` option
+pub const MODEL_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4o-mini", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4.1", 1_047_576),
+    ("o1", 200_000),
+    ("o3-mini", 200_000),
+    ("claude-3-5-sonnet", 200_000),
+    ("claude-3-7-sonnet", 200_000),
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini-2.0-flash", 1_048_576),
+];
+
+/// File signatures (magic numbers) of common binary formats, checked against
+/// the first bytes of a file regardless of its extension
+pub const BINARY_MAGIC_NUMBERS: &[(&str, &[u8])] = &[
+    ("png", b"\x89PNG\r\n\x1a\n"),
+    ("elf", b"\x7fELF"),
+    ("pdf", b"%PDF-"),
+    ("zip", b"PK\x03\x04"),
+    ("gzip", b"\x1f\x8b"),
+];
+
+/// Minimum number of lines a shared leading block needs before
+/// `dedupe_boilerplate` replaces it with a reference
+pub const BOILERPLATE_MIN_LINES: usize = 3;
+
+/// Build and package manifests that `manifests_first` moves to the top of the output
+pub const MANIFEST_FILES: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "go.mod",
+    "setup.py",
+    "requirements.txt",
+    "Gemfile",
+    "pom.xml",
+    "build.gradle",
+    "composer.json",
+];
+
+/// Named regexes for credentials, reported line by line by `secret_scan` and
+/// replaced with `[REDACTED]` by `redact_secrets`. A private key matches its
+/// whole block when the END line is present, so redaction covers the body.
+pub const SECRET_PATTERNS: &[(&str, &str)] = &[
+    ("aws-access-key-id", r"\b(AKIA|ASIA)[0-9A-Z]{16}\b"),
+    ("github-token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+    ("slack-token", r"\bxox[abposr]-[A-Za-z0-9-]{10,}"),
+    (
+        "private-key",
+        r"(?s)-----BEGIN [A-Z ]*PRIVATE KEY-----(.*?-----END [A-Z ]*PRIVATE KEY-----)?",
+    ),
+    (
+        "generic-api-key",
+        r"(?i)\b[A-Z0-9_]*(API[_-]?KEY|SECRET|TOKEN|PASSWORD)[A-Z0-9_]*\s*[:=]\s*[^\s]+",
+    ),
+];
+
+/// Name of the yek-only ignore files honored in every directory of the walk,
+/// alongside `.gitignore` and with gitignore syntax
+pub const YEKIGNORE_FILE: &str = ".yekignore";
+
+/// Directory names holding vendored dependencies, pruned from the walk
+/// unless `include_vendored` is set
+pub const VENDORED_DIRS: &[&str] = &[
+    "vendor",
+    "third_party",
+    "third-party",
+    "node_modules",
+    "bower_components",
+    "jspm_packages",
+    ".venv",
+    "venv",
+    "site-packages",
+    "Pods",
+    "Carthage",
+];
+
+/// Files or directories marking a project root for `--auto-root`, in order of preference
+pub const PROJECT_ROOT_MARKERS: &[&str] = &[".git", "yek.toml", "Cargo.toml"];
@@ -0,0 +1,158 @@
+use regex::Regex;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::models::ProcessedFile;
+
+/// Extensions tried, in order, when a JS/TS import omits one
+const JS_EXTENSIONS: &[&str] = &["ts", "tsx", "js", "jsx", "mjs", "cjs"];
+
+static RUST_MOD: OnceLock<Regex> = OnceLock::new();
+static RUST_USE_CRATE: OnceLock<Regex> = OnceLock::new();
+static JS_IMPORT: OnceLock<Regex> = OnceLock::new();
+
+/// Edges `(from, to)` between packed files, found by a lightweight scan of
+/// Rust `mod`/`use crate::` items and relative JS/TS imports. Imports that
+/// don't resolve to another packed file (external crates, packages) are left out.
+pub fn dependency_graph(files: &[ProcessedFile]) -> Vec<(String, String)> {
+    let known: HashSet<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    let mut edges = BTreeSet::new();
+    for file in files {
+        let targets = match Path::new(&file.rel_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+        {
+            Some("rs") => rust_imports(&file.rel_path, &file.content, &known),
+            Some(ext) if JS_EXTENSIONS.contains(&ext) => {
+                js_imports(&file.rel_path, &file.content, &known)
+            }
+            _ => continue,
+        };
+        for target in targets {
+            if target != file.rel_path {
+                edges.insert((file.rel_path.clone(), target));
+            }
+        }
+    }
+    edges.into_iter().collect()
+}
+
+/// Render edges as a "Dependency graph:" section, or nothing if there are none
+pub fn render_dependency_graph(edges: &[(String, String)]) -> String {
+    if edges.is_empty() {
+        return String::new();
+    }
+    let mut output = String::from("Dependency graph:\n");
+    for (from, to) in edges {
+        output.push_str(&format!("{} -> {}\n", from, to));
+    }
+    output.push('\n');
+    output
+}
+
+fn rust_imports(rel_path: &str, content: &str, known: &HashSet<&str>) -> Vec<String> {
+    let mod_re = RUST_MOD.get_or_init(|| {
+        Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_][A-Za-z0-9_]*)\s*;")
+            .expect("mod regex should compile")
+    });
+    let use_re = RUST_USE_CRATE.get_or_init(|| {
+        Regex::new(r"\bcrate::((?:[A-Za-z_][A-Za-z0-9_]*::)*[A-Za-z_][A-Za-z0-9_]*)")
+            .expect("use regex should compile")
+    });
+
+    let path = Path::new(rel_path);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    // `mod foo;` in lib.rs/main.rs/mod.rs lives next to it, elsewhere in a directory named after the file
+    let mod_dir = if matches!(stem, "lib" | "main" | "mod") {
+        dir.to_path_buf()
+    } else {
+        dir.join(stem)
+    };
+
+    let mut targets = Vec::new();
+    for caps in mod_re.captures_iter(content) {
+        if let Some(target) = rust_module_file(&mod_dir, &[&caps[1]], known) {
+            targets.push(target);
+        }
+    }
+
+    if let Some(root) = crate_root(dir, known) {
+        for caps in use_re.captures_iter(content) {
+            let segments: Vec<&str> = caps[1].split("::").collect();
+            // The longest module path that names a packed file wins
+            if let Some(target) = (1..=segments.len())
+                .rev()
+                .find_map(|len| rust_module_file(&root, &segments[..len], known))
+            {
+                targets.push(target);
+            }
+        }
+    }
+    targets
+}
+
+/// Nearest directory (from `dir` upwards) holding a packed lib.rs or main.rs
+fn crate_root(dir: &Path, known: &HashSet<&str>) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| {
+            ["lib.rs", "main.rs"]
+                .iter()
+                .any(|root| known.contains(slash_path(&ancestor.join(root)).as_str()))
+        })
+        .map(Path::to_path_buf)
+}
+
+fn rust_module_file(base: &Path, segments: &[&str], known: &HashSet<&str>) -> Option<String> {
+    let module = segments.iter().fold(base.to_path_buf(), |p, s| p.join(s));
+    [module.with_extension("rs"), module.join("mod.rs")]
+        .iter()
+        .map(|candidate| slash_path(candidate))
+        .find(|candidate| known.contains(candidate.as_str()))
+}
+
+fn js_imports(rel_path: &str, content: &str, known: &HashSet<&str>) -> Vec<String> {
+    let import_re = JS_IMPORT.get_or_init(|| {
+        Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)["'](\.{1,2}/[^"']*)["']"#)
+            .expect("import regex should compile")
+    });
+
+    let dir = Path::new(rel_path).parent().unwrap_or(Path::new(""));
+    import_re
+        .captures_iter(content)
+        .filter_map(|caps| {
+            let base = slash_path(&normalize(&dir.join(&caps[1])));
+            std::iter::once(base.clone())
+                .chain(JS_EXTENSIONS.iter().map(|ext| format!("{}.{}", base, ext)))
+                .chain(
+                    JS_EXTENSIONS
+                        .iter()
+                        .map(|ext| format!("{}/index.{}", base, ext)),
+                )
+                .find(|candidate| known.contains(candidate.as_str()))
+        })
+        .collect()
+}
+
+/// Resolve `.` and `..` components without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
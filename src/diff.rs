@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::models::ProcessedFile;
+
+/// Collect a unified diff for every file that changed between `since` and the
+/// working tree of the repository containing `path` (untracked files included).
+/// Keys are canonical absolute paths of the changed files.
+pub fn get_diffs_since_git2(path: &Path, since: &str) -> Result<HashMap<PathBuf, String>> {
+    let repo = git2::Repository::discover(path)
+        .map_err(|e| anyhow!("'{}' is not inside a git repository: {}", path.display(), e))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("Repository at '{}' has no working tree", path.display()))?
+        .canonicalize()?;
+
+    let tree = repo
+        .revparse_single(since)
+        .and_then(|obj| obj.peel_to_tree())
+        .map_err(|e| anyhow!("Failed to resolve '{}': {}", since, e))?;
+
+    let mut opts = git2::DiffOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    let diff = repo
+        .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut opts))
+        .map_err(|e| anyhow!("Failed to diff against '{}': {}", since, e))?;
+
+    let mut diffs = HashMap::new();
+    for idx in 0..diff.deltas().len() {
+        let Some(mut patch) = git2::Patch::from_diff(&diff, idx)? else {
+            continue;
+        };
+        let Some(file_path) = patch.delta().new_file().path().map(Path::to_path_buf) else {
+            continue;
+        };
+        let buf = patch.to_buf()?;
+        diffs.insert(
+            workdir.join(file_path),
+            String::from_utf8_lossy(&buf).to_string(),
+        );
+    }
+
+    Ok(diffs)
+}
+
+/// Canonical absolute path of a processed file found under `input_path`
+fn absolute_path(input_path: &Path, rel_path: &str) -> Option<PathBuf> {
+    let base_dir = if input_path.is_dir() {
+        input_path
+    } else {
+        input_path.parent().unwrap_or(Path::new("."))
+    };
+    [base_dir.join(rel_path), PathBuf::from(rel_path)]
+        .into_iter()
+        .find_map(|candidate| candidate.canonicalize().ok())
+}
+
+/// Replace the content of each file found under `input_path` with its diff
+/// since `since`, dropping files that did not change.
+pub fn replace_with_diffs(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    since: &str,
+) -> Result<Vec<ProcessedFile>> {
+    let diffs = get_diffs_since_git2(input_path, since)?;
+
+    Ok(files
+        .into_iter()
+        .filter_map(|file| {
+            let abs_path = absolute_path(input_path, &file.rel_path)?;
+            let Some(patch) = diffs.get(&abs_path) else {
+                debug!("Skipping unchanged file: {}", file.rel_path);
+                return None;
+            };
+            Some(ProcessedFile::new_with_category(
+                file.rel_path,
+                patch.clone(),
+                file.priority,
+                file.file_index,
+                file.category,
+            ))
+        })
+        .collect())
+}
+
+/// Put each changed file's diff since `since` ahead of its full content.
+/// Unchanged files are kept as they are.
+pub fn prepend_diffs(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    since: &str,
+) -> Result<Vec<ProcessedFile>> {
+    let diffs = get_diffs_since_git2(input_path, since)?;
+
+    Ok(files
+        .into_iter()
+        .map(|file| {
+            let patch =
+                absolute_path(input_path, &file.rel_path).and_then(|abs_path| diffs.get(&abs_path));
+            match patch {
+                Some(patch) => ProcessedFile::new_with_category(
+                    file.rel_path,
+                    format!("{}\n{}", patch.trim_end(), file.content),
+                    file.priority,
+                    file.file_index,
+                    file.category,
+                ),
+                None => file,
+            }
+        })
+        .collect())
+}
+
+/// Files changed by any commit in `range` (`A..B`: reachable from B but not
+/// from A) of the repository containing `path`. Renamed files are reported
+/// under their new path. Paths are canonical absolute paths.
+pub fn get_changed_paths_in_range(path: &Path, range: &str) -> Result<HashSet<PathBuf>> {
+    let repo = git2::Repository::discover(path)
+        .map_err(|e| anyhow!("'{}' is not inside a git repository: {}", path.display(), e))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("Repository at '{}' has no working tree", path.display()))?
+        .canonicalize()?;
+
+    let mut revwalk = repo.revwalk()?;
+    revwalk
+        .push_range(range)
+        .map_err(|e| anyhow!("Failed to resolve range '{}': {}", range, e))?;
+
+    let mut changed = HashSet::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid?)?;
+        let tree = commit.tree()?;
+        let parent_tree = commit
+            .parent(0)
+            .ok()
+            .map(|parent| parent.tree())
+            .transpose()?;
+        let mut diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+        for delta in diff.deltas() {
+            if delta.status() == git2::Delta::Deleted {
+                continue;
+            }
+            if let Some(file_path) = delta.new_file().path() {
+                changed.insert(workdir.join(file_path));
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Keep only the files found under `input_path` that a commit in `range` changed
+pub fn restrict_to_range(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    range: &str,
+) -> Result<Vec<ProcessedFile>> {
+    let changed = get_changed_paths_in_range(input_path, range)?;
+    Ok(keep_changed(input_path, files, &changed))
+}
+
+/// Files that differ between `head` and its merge base with `base`, like
+/// `git diff --name-only base...head`. Unlike a commit range, a change that
+/// was later reverted doesn't count. Deleted files are left out and renamed
+/// files are reported under their new path. Paths are canonical absolute paths.
+pub fn get_changed_paths_between(path: &Path, base: &str, head: &str) -> Result<HashSet<PathBuf>> {
+    let repo = git2::Repository::discover(path)
+        .map_err(|e| anyhow!("'{}' is not inside a git repository: {}", path.display(), e))?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| anyhow!("Repository at '{}' has no working tree", path.display()))?
+        .canonicalize()?;
+
+    let resolve = |rev: &str| {
+        repo.revparse_single(rev)
+            .and_then(|obj| obj.peel_to_commit())
+            .map_err(|e| anyhow!("Failed to resolve '{}': {}", rev, e))
+    };
+    let base_commit = resolve(base)?;
+    let head_commit = resolve(head)?;
+    let merge_base = repo
+        .merge_base(base_commit.id(), head_commit.id())
+        .map_err(|e| anyhow!("No merge base between '{}' and '{}': {}", base, head, e))?;
+
+    let base_tree = repo.find_commit(merge_base)?.tree()?;
+    let head_tree = head_commit.tree()?;
+    let mut diff = repo.diff_tree_to_tree(Some(&base_tree), Some(&head_tree), None)?;
+    diff.find_similar(Some(git2::DiffFindOptions::new().renames(true)))?;
+
+    Ok(diff
+        .deltas()
+        .filter(|delta| delta.status() != git2::Delta::Deleted)
+        .filter_map(|delta| delta.new_file().path().map(|p| workdir.join(p)))
+        .collect())
+}
+
+/// Keep only the files found under `input_path` that differ between `base`
+/// and `head` (see [`get_changed_paths_between`]). Outside a git repository
+/// every file is kept, so the run falls back to a full serialization.
+pub fn restrict_to_git_diff(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    base: &str,
+    head: &str,
+) -> Result<Vec<ProcessedFile>> {
+    if git2::Repository::discover(input_path).is_err() {
+        debug!(
+            "{} is not inside a git repository; packing all files",
+            input_path.display()
+        );
+        return Ok(files);
+    }
+    let changed = get_changed_paths_between(input_path, base, head)?;
+    Ok(keep_changed(input_path, files, &changed))
+}
+
+fn keep_changed(
+    input_path: &Path,
+    files: Vec<ProcessedFile>,
+    changed: &HashSet<PathBuf>,
+) -> Vec<ProcessedFile> {
+    files
+        .into_iter()
+        .filter(|file| {
+            absolute_path(input_path, &file.rel_path).is_some_and(|abs| changed.contains(&abs))
+        })
+        .collect()
+}
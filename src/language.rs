@@ -0,0 +1,71 @@
+use std::path::Path;
+
+/// Lockfiles whose extension doesn't say so
+const LOCK_FILE_NAMES: &[&str] = &[
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "pnpm-lock.yaml",
+    "go.sum",
+];
+
+/// Language name of a file, from its name or extension: `rust` for
+/// `main.rs`, `lock` for `Cargo.lock` or `package-lock.json`, `dts` for
+/// TypeScript declarations, `minified` for `.min.js`/`.min.css`. Files
+/// without an extension, or with one not in the table, give "". Shared by
+/// `FILE_LANG`, code fences, embeddings metadata and `exclude_languages`.
+pub fn detect_language(path: &str) -> String {
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    if LOCK_FILE_NAMES.contains(&file_name.as_str()) {
+        return "lock".to_string();
+    }
+    if file_name.ends_with(".d.ts") {
+        return "dts".to_string();
+    }
+    if file_name.ends_with(".min.js") || file_name.ends_with(".min.css") {
+        return "minified".to_string();
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    let Some(extension) = extension else {
+        return String::new();
+    };
+    let language = match extension.as_str() {
+        "c" | "css" | "go" | "html" | "java" | "json" | "lock" | "lua" | "php" | "scala"
+        | "scss" | "sql" | "swift" | "toml" | "xml" | "yaml" => extension.as_str(),
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "rb" => "ruby",
+        "kt" | "kts" => "kotlin",
+        "cs" => "csharp",
+        "cc" | "cpp" | "cxx" | "hpp" | "hxx" => "cpp",
+        "h" => "c",
+        "sh" | "bash" | "zsh" => "shell",
+        "md" | "markdown" => "markdown",
+        "yml" => "yaml",
+        "txt" => "text",
+        _ => "",
+    };
+    language.to_string()
+}
+
+/// True if `path`'s detected language is one of `languages` (case-insensitive)
+pub fn is_language_excluded(path: &str, languages: &[String]) -> bool {
+    if languages.is_empty() {
+        return false;
+    }
+    let language = detect_language(path);
+    !language.is_empty()
+        && languages
+            .iter()
+            .any(|excluded| excluded.eq_ignore_ascii_case(&language))
+}
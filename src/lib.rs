@@ -2,32 +2,47 @@ use anyhow::anyhow;
 use anyhow::Result;
 use bytesize::ByteSize;
 use content_inspector::{inspect, ContentType};
+use flate2::{write::GzEncoder, Compression};
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{self, Read},
-    path::Path,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
     str::FromStr,
     sync::OnceLock,
 };
 use tiktoken_rs::CoreBPE;
 
+pub mod blame;
+pub mod cache;
 pub mod category;
+pub mod comments;
 pub mod config;
 pub mod defaults;
+pub mod depgraph;
+pub mod diff;
 pub mod error;
+pub mod language;
 pub mod models;
 pub mod parallel;
 pub mod pipeline;
 pub mod priority;
+pub mod progress;
 pub mod repository;
+pub mod secrets;
 pub mod tree;
 
 use config::YekConfig;
+use defaults::{
+    BOILERPLATE_MIN_LINES, DEFAULT_GIT_BOOST_STRATEGY, MANIFEST_FILES, MARKDOWN_OUTPUT_TEMPLATE,
+};
+use language::detect_language;
 use models::ProcessedFile;
-use parallel::process_files_parallel;
-use priority::compute_recentness_boost;
+use parallel::process_files_parallel_with_progress;
+use priority::{compute_recentness_boost, PriorityScorer};
+use progress::{ProgressCallback, ProgressEvent};
 use tree::generate_tree;
 
 // Add a static BPE encoder for reuse
@@ -42,6 +57,32 @@ fn get_tokenizer() -> &'static CoreBPE {
 /// Check if a file is likely text or binary by reading only a small chunk.
 /// This avoids reading large files fully just to detect their type.
 pub fn is_text_file(path: &Path, user_binary_extensions: &[String]) -> io::Result<bool> {
+    is_text_file_with_magic(path, user_binary_extensions, true)
+}
+
+/// Like [`is_text_file`], optionally also treating files that start with a
+/// known binary signature (see `BINARY_MAGIC_NUMBERS`) as binary.
+pub fn is_text_file_with_magic(
+    path: &Path,
+    user_binary_extensions: &[String],
+    magic_detection: bool,
+) -> io::Result<bool> {
+    is_text_file_sampled(
+        path,
+        user_binary_extensions,
+        magic_detection,
+        defaults::DEFAULT_BINARY_DETECTION_BYTES,
+    )
+}
+
+/// Like [`is_text_file_with_magic`], deciding from the first `sample_bytes`
+/// bytes of the file (see `binary_detection_bytes`).
+pub fn is_text_file_sampled(
+    path: &Path,
+    user_binary_extensions: &[String],
+    magic_detection: bool,
+    sample_bytes: usize,
+) -> io::Result<bool> {
     // If extension is known to be binary, skip quickly
     if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
         if user_binary_extensions.iter().any(|bin_ext| bin_ext == ext) {
@@ -50,17 +91,77 @@ pub fn is_text_file(path: &Path, user_binary_extensions: &[String]) -> io::Resul
     }
 
     // Short partial read to check if it's binary or text
-    const INSPECTION_BYTES: usize = 8192;
-    let mut file = File::open(path)?;
-    let mut buf = vec![0u8; INSPECTION_BYTES];
-    let n = file.read(&mut buf)?;
-    buf.truncate(n);
+    let mut buf = Vec::with_capacity(sample_bytes);
+    File::open(path)?
+        .take(sample_bytes as u64)
+        .read_to_end(&mut buf)?;
 
-    Ok(inspect(&buf) != ContentType::BINARY)
+    if magic_detection && has_binary_magic(&buf) {
+        return Ok(false);
+    }
+    Ok(!looks_binary(&buf))
+}
+
+/// True if a sample from the start of a file looks binary: it contains a
+/// null byte, or more than `BINARY_CONTROL_RATIO` of its bytes are control
+/// characters. UTF-16 text with a byte order mark counts as text despite its
+/// interleaved nulls.
+pub fn looks_binary(sample: &[u8]) -> bool {
+    match inspect(sample) {
+        ContentType::BINARY => true,
+        ContentType::UTF_8 | ContentType::UTF_8_BOM => {
+            let control = sample
+                .iter()
+                .filter(|&&b| (b < 0x20 && !b"\t\n\r\x0c\x1b".contains(&b)) || b == 0x7f)
+                .count();
+            !sample.is_empty()
+                && control as f64 / sample.len() as f64 > defaults::BINARY_CONTROL_RATIO
+        }
+        _ => false,
+    }
+}
+
+/// True if `bytes` starts with the signature of a known binary format
+pub fn has_binary_magic(bytes: &[u8]) -> bool {
+    defaults::BINARY_MAGIC_NUMBERS
+        .iter()
+        .any(|(_, magic)| bytes.starts_with(magic))
 }
 
 /// Main entrypoint for serialization, used by CLI and tests
 pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)> {
+    serialize_repo_inner(config, None, None)
+}
+
+/// Like `serialize_repo`, but reports the git history scan, the files the
+/// walk discovers and each file processed to `progress`.
+pub fn serialize_repo_with_progress(
+    config: &YekConfig,
+    progress: ProgressCallback,
+) -> Result<(String, Vec<ProcessedFile>)> {
+    serialize_repo_inner(config, None, Some(&progress))
+}
+
+/// Like `serialize_repo`, but every file's priority comes from `scorer`
+/// instead of the built-in rules, categories and git recency.
+pub fn serialize_repo_with_scorer(
+    config: &YekConfig,
+    scorer: &dyn PriorityScorer,
+) -> Result<(String, Vec<ProcessedFile>)> {
+    serialize_repo_inner(config, Some(scorer), None)
+}
+
+fn serialize_repo_inner(
+    config: &YekConfig,
+    scorer: Option<&dyn PriorityScorer>,
+    progress: Option<&ProgressCallback>,
+) -> Result<(String, Vec<ProcessedFile>)> {
+    if config.strict {
+        config
+            .validate()
+            .map_err(|e| anyhow!("Invalid configuration: {}", e))?;
+    }
+
     // Validate input paths and warn about non-existent ones
     let mut non_existent_paths = Vec::new();
 
@@ -75,7 +176,25 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
     // If we have non-existent paths, warn the user
     if !non_existent_paths.is_empty() {
         for path in &non_existent_paths {
-            eprintln!("Warning: Path '{}' does not exist", path);
+            tracing::warn!("Path '{}' does not exist", path);
+        }
+    }
+
+    // Priority rules are regexes; one that doesn't compile would never match
+    for rule in &config.priority_rules {
+        if let Err(e) = regex::Regex::new(&rule.pattern) {
+            if config.strict {
+                return Err(anyhow!(
+                    "Invalid configuration: priority_rules: Pattern '{}' is not a valid regex: {}",
+                    rule.pattern,
+                    e
+                ));
+            }
+            tracing::warn!(
+                "priority_rules: Pattern '{}' is not a valid regex and matches nothing: {}",
+                rule.pattern,
+                e
+            );
         }
     }
 
@@ -86,10 +205,16 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
         .filter_map(|path_str| {
             let repo_path = Path::new(path_str);
             if repo_path.is_dir() {
-                priority::get_recent_commit_times_git2(
+                let commit_times = priority::get_recent_commit_times_git2(
                     repo_path,
                     config.max_git_depth.try_into().unwrap_or(0),
-                )
+                );
+                if let Some(progress) = progress {
+                    progress(ProgressEvent::GitHistoryScanned {
+                        files: commit_times.as_ref().map_or(0, HashMap::len),
+                    });
+                }
+                commit_times
             } else {
                 None
             }
@@ -97,17 +222,46 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
         .flatten()
         .collect::<HashMap<String, u64>>();
 
-    // Compute a recentness-based boost
-    let recentness_boost =
-        compute_recentness_boost(&combined_commit_times, config.git_boost_max.unwrap_or(100));
-
     // Process files in parallel for each input path
     let merged_files = config
         .input_paths
         .par_iter()
         .map(|path_str| {
             let path = Path::new(path_str);
-            process_files_parallel(path, config, &recentness_boost)
+            let files =
+                process_files_parallel_with_progress(path, config, &HashMap::new(), progress)?;
+            let files = match &config.range {
+                Some(range) => diff::restrict_to_range(path, files, range)?,
+                None => files,
+            };
+            let files = match &config.git_diff_base {
+                Some(base) => diff::restrict_to_git_diff(
+                    path,
+                    files,
+                    base,
+                    config.git_diff_head.as_deref().unwrap_or("HEAD"),
+                )?,
+                None => files,
+            };
+            let files = if config.strip_comments {
+                files.into_par_iter().map(strip_file_comments).collect()
+            } else {
+                files
+            };
+            // Blame annotates real file lines, so it doesn't apply to packed diffs
+            let files = if config.blame && !config.diff_output && !config.changed_with_diff {
+                blame::annotate_with_blame(path, files, config.blame_max_files)
+            } else {
+                files
+            };
+            let since = config.since.as_deref().unwrap_or("HEAD");
+            if config.diff_output {
+                diff::replace_with_diffs(path, files, since)
+            } else if config.changed_with_diff {
+                diff::prepend_diffs(path, files, since)
+            } else {
+                Ok(files)
+            }
         })
         .collect::<Result<Vec<Vec<ProcessedFile>>>>()?
         .into_iter()
@@ -116,16 +270,159 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
 
     let mut files = merged_files;
 
-    // Sort final (priority asc, then file_index asc)
-    files.par_sort_by(|a, b| {
-        a.priority
-            .cmp(&b.priority)
-            .then_with(|| a.rel_path.cmp(&b.rel_path))
-    });
+    // Redact secrets before anything is sized or written
+    let redact_patterns = secrets::redaction_patterns(config)?;
+    if !redact_patterns.is_empty() {
+        let (redacted, count) = secrets::redact_secrets(files, &redact_patterns);
+        files = redacted;
+        if count > 0 {
+            tracing::info!("Redacted {} secrets", count);
+        }
+    }
+
+    // Shorten very long lines before anything is sized
+    if let Some(max_len) = config.max_line_length {
+        let wrap = config.long_line_strategy == "wrap";
+        files = files
+            .into_par_iter()
+            .map(|file| {
+                if !file
+                    .content
+                    .lines()
+                    .any(|line| line.chars().count() > max_len)
+                {
+                    return file;
+                }
+                ProcessedFile::new_with_category(
+                    file.rel_path,
+                    limit_line_length(&file.content, max_len, wrap),
+                    file.priority,
+                    file.file_index,
+                    file.category,
+                )
+            })
+            .collect();
+    }
+
+    // In token mode, cut files that alone would eat too much of the budget
+    if let (true, Some(budget)) = (config.token_mode, config.max_file_tokens) {
+        files = files
+            .into_par_iter()
+            .map(
+                |file| match limit_file_tokens(&file.content, budget, config.sample_large_files) {
+                    Cow::Borrowed(_) => file,
+                    Cow::Owned(content) => ProcessedFile::new_with_category(
+                        file.rel_path,
+                        content,
+                        file.priority,
+                        file.file_index,
+                        file.category,
+                    ),
+                },
+            )
+            .collect();
+    }
+
+    // Unset, the git boost is added to rule scores; a git_boost_max of 0 turns it off
+    let git_boost_strategy = Some(
+        config
+            .git_boost_strategy
+            .as_deref()
+            .unwrap_or(DEFAULT_GIT_BOOST_STRATEGY),
+    )
+    .filter(|_| scorer.is_none() && config.git_boost_max != Some(0));
+
+    // Git history is only walked when something below needs per-file change times
+    let change_times =
+        if config.git_since_filter || config.max_age.is_some() || git_boost_strategy.is_some() {
+            last_change_times(config)?
+        } else {
+            Vec::new()
+        };
+
+    // Optionally drop files with no commits in the git_since window
+    if config.git_since_filter {
+        files = drop_files_outside_window(files, &change_times);
+    }
+
+    // A custom scorer replaces the computed priorities; otherwise the git boost may be combined in
+    if let Some(scorer) = scorer {
+        files.par_iter_mut().for_each(|file| {
+            let git_time = combined_commit_times.get(&file.rel_path).copied();
+            file.priority = scorer.score(&file.rel_path, &file.content, git_time);
+        });
+    } else if let Some(strategy) = git_boost_strategy {
+        files = apply_git_boost(files, config, &change_times, strategy);
+    }
+
+    // Optionally drop files that have not changed within the maximum age
+    if let Some(max_age) = &config.max_age {
+        let max_age_secs = parse_age(max_age)?;
+        files = drop_stale_files(files, config, &change_times, max_age_secs);
+    }
+
+    // Optionally keep only the N largest/smallest files, regardless of priority
+    if let (Some(select), Some(count)) = (&config.select, config.select_count) {
+        files = select_by_size(files, select, count);
+    }
+
+    // Optionally drop near-duplicates, keeping the highest-priority copy
+    if let Some(threshold) = config.near_duplicate_threshold {
+        files = remove_near_duplicates(files, threshold);
+    }
+
+    // Keep only the max_files most important files once priorities are final
+    if let Some(max_files) = config.max_files {
+        if files.len() > max_files {
+            let found = files.len();
+            files = keep_highest_priority(files, max_files);
+            tracing::warn!(
+                "Found {} files, more than max_files ({}); dropped the {} lowest-priority files",
+                found,
+                max_files,
+                found - max_files
+            );
+        }
+    }
+
+    // Keep the most important files that fit max_total_size, in tokens or bytes
+    if let Some(budget) = total_size_limit(config)? {
+        let found = files.len();
+        let (kept, omitted_size) = keep_within_total_size(files, budget, config)?;
+        files = kept;
+        if files.len() < found {
+            tracing::warn!(
+                "Dropped the {} lowest-priority files ({}) to stay within max_total_size ({})",
+                found - files.len(),
+                format_budget(omitted_size, config.token_mode, &config.size_display),
+                format_budget(budget, config.token_mode, &config.size_display)
+            );
+        }
+    }
+
+    // Sort final (priority in priority_order, then path asc), or by path alone with no_sort
+    files.par_sort_by(|a, b| output_order(a, b, config));
+
+    // Optionally emit identical contents once, under the first path in output order
+    if config.dedup_identical {
+        files = merge_identical_files(files);
+    }
 
     // If no files were processed and we had non-existent paths, provide additional context
     if files.is_empty() && !non_existent_paths.is_empty() {
-        eprintln!("Warning: No files were processed. All specified paths were non-existent or contained no valid files.");
+        tracing::warn!("No files were processed. All specified paths were non-existent or contained no valid files.");
+    }
+
+    // Audit only: report likely secrets without touching the content
+    if config.secret_scan {
+        for finding in secrets::scan_secrets(&files) {
+            tracing::warn!(
+                "Possible secret ({}) in {}:{}",
+                finding.pattern,
+                finding.path,
+                finding.line
+            );
+        }
     }
 
     // Build the final output string
@@ -139,6 +436,509 @@ pub fn serialize_repo(config: &YekConfig) -> Result<(String, Vec<ProcessedFile>)
     Ok((output_string, files))
 }
 
+/// Number of files across all input paths that pass the walk and ignore
+/// filters. Content is never read, so this is much cheaper than `serialize_repo`.
+pub fn count_includable_files(config: &YekConfig) -> Result<usize> {
+    config
+        .input_paths
+        .par_iter()
+        .map(|path_str| parallel::count_includable_files(Path::new(path_str), config))
+        .sum()
+}
+
+/// Run `post_write_command` through the shell for a freshly written output
+/// file, with `{file}` replaced by its path. A failing command is an error
+/// naming the file, so each file's failure surfaces on its own. With `sh`
+/// the path is passed as `$1` rather than spliced into the script, so spaces
+/// or shell syntax in it stay part of the path; `{file}` may be quoted or not.
+pub fn run_post_write_command(command: &str, file: &Path) -> Result<()> {
+    let status = if cfg!(windows) {
+        let quoted = format!("\"{}\"", file.to_string_lossy());
+        std::process::Command::new("cmd")
+            .args(["/C", &expand_file_placeholder(command, &quoted)])
+            .status()
+    } else {
+        std::process::Command::new("sh")
+            .args(["-c", &expand_file_placeholder(command, "\"$1\"")])
+            .arg("sh")
+            .arg(file)
+            .status()
+    }
+    .map_err(|e| anyhow!("post_write_command: cannot run '{}': {}", command, e))?;
+
+    if !status.success() {
+        return Err(anyhow!(
+            "post_write_command: '{}' failed for {} ({})",
+            command,
+            file.display(),
+            status
+        ));
+    }
+    Ok(())
+}
+
+/// Replace `{file}` in a shell command with `quoted_file`, an already quoted
+/// path. Quotes the user put around the placeholder are dropped first.
+fn expand_file_placeholder(command: &str, quoted_file: &str) -> String {
+    command
+        .replace("\"{file}\"", "{file}")
+        .replace("'{file}'", "{file}")
+        .replace("{file}", quoted_file)
+}
+
+/// Take an exclusive advisory lock on `output_dir` so concurrent runs
+/// targeting the same directory can't clobber each other's output. Fails
+/// immediately if another run holds the lock; it is released when the
+/// returned file is dropped. The lock file lives in the temp directory, named
+/// after the canonical output directory, so nothing is left in `output_dir`.
+#[cfg(feature = "lock")]
+pub fn lock_output_dir(output_dir: &str) -> Result<File> {
+    use fs2::FileExt;
+
+    let dir = Path::new(output_dir);
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let key = cache::content_hash(dir.to_string_lossy().as_bytes());
+    let lock_path = std::env::temp_dir().join(format!("yek-{}.lock", &key[..16]));
+    let lock_file = File::create(&lock_path)
+        .map_err(|e| anyhow!("output_dir: cannot create '{}': {}", lock_path.display(), e))?;
+    lock_file.try_lock_exclusive().map_err(|_| {
+        anyhow!(
+            "output_dir: '{}' is locked by another yek run; wait for it to finish or use a different output_dir",
+            output_dir
+        )
+    })?;
+    Ok(lock_file)
+}
+
+/// Strip comments from a file's content, based on its extension
+fn strip_file_comments(file: ProcessedFile) -> ProcessedFile {
+    let ext = Path::new(&file.rel_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("");
+    let content = comments::strip_comments_for(&file.content, ext);
+    if content == file.content {
+        return file;
+    }
+    ProcessedFile::new_with_category(
+        file.rel_path,
+        content,
+        file.priority,
+        file.file_index,
+        file.category,
+    )
+}
+
+/// Drop files last changed more than `max_age_secs` ago. Inside a git
+/// repository the last commit touching the file counts; elsewhere its mtime.
+/// Files with no known time (e.g. untracked, or older than `max_git_depth`
+/// commits) are kept unless `max_age_drop_unknown` is set.
+fn drop_stale_files(
+    files: Vec<ProcessedFile>,
+    config: &YekConfig,
+    change_times: &ChangeTimes,
+    max_age_secs: u64,
+) -> Vec<ProcessedFile> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let cutoff = now.saturating_sub(max_age_secs);
+
+    files
+        .into_iter()
+        .filter(|file| {
+            let last_change = change_times.iter().find_map(|(base_dir, times)| {
+                let abs_path = base_dir.join(&file.rel_path).canonicalize().ok()?;
+                match times {
+                    Some(times) => times.get(&abs_path).copied(),
+                    None => std::fs::metadata(&abs_path)
+                        .and_then(|meta| meta.modified())
+                        .ok()?
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .ok()
+                        .map(|d| d.as_secs()),
+                }
+            });
+            match last_change {
+                Some(time) => time >= cutoff,
+                None => !config.max_age_drop_unknown,
+            }
+        })
+        .collect()
+}
+
+/// Base directory of each input path with the git last change times of its
+/// repository (None outside git), limited to the `git_since` window. Used by
+/// `git_since_filter`, `max_age` and `git_boost_strategy`.
+type ChangeTimes = Vec<(std::path::PathBuf, Option<HashMap<std::path::PathBuf, u64>>)>;
+
+fn last_change_times(config: &YekConfig) -> Result<ChangeTimes> {
+    let since = config.git_since.as_deref().map(priority::GitSince::parse);
+    config
+        .input_paths
+        .iter()
+        .map(|path_str| {
+            let path = Path::new(path_str);
+            let base_dir = if path.is_dir() {
+                path
+            } else {
+                path.parent().unwrap_or(Path::new("."))
+            };
+            let times = priority::get_last_change_times_git2(
+                base_dir,
+                config.max_git_depth.try_into().unwrap_or(0),
+                since.as_ref(),
+            )?;
+            Ok((base_dir.to_path_buf(), times))
+        })
+        .collect()
+}
+
+/// Keep only files changed within the `git_since` window. Inputs outside git
+/// have no history to filter by, so their files are all kept.
+fn drop_files_outside_window(
+    files: Vec<ProcessedFile>,
+    change_times: &ChangeTimes,
+) -> Vec<ProcessedFile> {
+    files
+        .into_iter()
+        .filter(|file| {
+            change_times.iter().any(|(base_dir, times)| {
+                let Ok(abs_path) = base_dir.join(&file.rel_path).canonicalize() else {
+                    return false;
+                };
+                times
+                    .as_ref()
+                    .is_none_or(|times| times.contains_key(&abs_path))
+            })
+        })
+        .collect()
+}
+
+/// Combine each file's priority with a git recency boost (0..`git_boost_max`,
+/// newest change highest) using `strategy`: "add" sums them, "max" keeps the
+/// larger, and "weighted" blends them with `git_boost_weight`. Files without a
+/// known commit time get a boost of 0.
+fn apply_git_boost(
+    mut files: Vec<ProcessedFile>,
+    config: &YekConfig,
+    change_times: &ChangeTimes,
+    strategy: &str,
+) -> Vec<ProcessedFile> {
+    let file_times: HashMap<String, u64> = files
+        .iter()
+        .filter_map(|file| {
+            change_times.iter().find_map(|(base_dir, times)| {
+                let abs_path = base_dir.join(&file.rel_path).canonicalize().ok()?;
+                let time = times.as_ref()?.get(&abs_path)?;
+                Some((file.rel_path.clone(), *time))
+            })
+        })
+        .collect();
+    let boosts = compute_recentness_boost(&file_times, config.git_boost_max.unwrap_or(100));
+
+    for file in &mut files {
+        let boost = boosts.get(&file.rel_path).copied().unwrap_or(0);
+        file.priority = match strategy {
+            "max" => file.priority.max(boost),
+            "weighted" => {
+                let weight = config.git_boost_weight;
+                ((1.0 - weight) * file.priority as f64 + weight * boost as f64).round() as i32
+            }
+            _ => file.priority.saturating_add(boost),
+        };
+    }
+    files
+}
+
+/// Parse an age like "30d", "12h", "6w" or "1y" into seconds
+pub fn parse_age(age: &str) -> anyhow::Result<u64> {
+    let age = age.trim();
+    let split = age
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| anyhow!("max_age: Missing unit in '{}' (use h, d, w or y)", age))?;
+    let (amount, unit) = age.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow!("max_age: Invalid age '{}'", age))?;
+    let unit_secs = match unit.trim().to_ascii_lowercase().as_str() {
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        other => return Err(anyhow!("max_age: Unknown unit '{}' in '{}'", other, age)),
+    };
+    amount
+        .checked_mul(unit_secs)
+        .ok_or_else(|| anyhow!("max_age: '{}' is too large", age))
+}
+
+/// Hard-wrap every line longer than `max_len` characters, or cut it there
+/// and note how many characters were dropped
+pub fn limit_line_length(content: &str, max_len: usize, wrap: bool) -> String {
+    let mut output = String::with_capacity(content.len());
+    for line in content.split_inclusive('\n') {
+        let (text, newline) = match line.strip_suffix('\n') {
+            Some(text) => (text, "\n"),
+            None => (line, ""),
+        };
+        let len = text.chars().count();
+        if len <= max_len {
+            output.push_str(line);
+            continue;
+        }
+
+        let chars: Vec<char> = text.chars().collect();
+        if wrap {
+            let wrapped: Vec<String> = chars
+                .chunks(max_len)
+                .map(|piece| piece.iter().collect())
+                .collect();
+            output.push_str(&wrapped.join("\n"));
+        } else {
+            output.extend(&chars[..max_len]);
+            output.push_str(&format!(" [... {} chars truncated]", len - max_len));
+        }
+        output.push_str(newline);
+    }
+    output
+}
+
+/// Cut `content` to `budget` tokens, noting how many were left out. With
+/// `sample`, keep the first and last `budget / 2` tokens so both the imports
+/// at the top and the code at the bottom survive; otherwise keep the start.
+pub fn limit_file_tokens(content: &str, budget: usize, sample: bool) -> Cow<'_, str> {
+    let tokens = get_tokenizer().encode_with_special_tokens(content);
+    if tokens.len() <= budget {
+        return Cow::Borrowed(content);
+    }
+
+    let marker = |omitted: usize| format!("\n... [omitted {} tokens] ...\n", omitted);
+    if sample {
+        let head_budget = budget.div_ceil(2);
+        let (head, head_kept) = decode_whole_chars(&tokens[..head_budget], false);
+        let (tail, tail_kept) =
+            decode_whole_chars(&tokens[tokens.len() - (budget - head_budget)..], true);
+        Cow::Owned(format!(
+            "{}{}{}",
+            head,
+            marker(tokens.len() - head_kept - tail_kept),
+            tail
+        ))
+    } else {
+        let (head, kept) = decode_whole_chars(&tokens[..budget], false);
+        Cow::Owned(format!("{}{}", head, marker(tokens.len() - kept)))
+    }
+}
+
+/// Decode a run of tokens cut from a longer text, dropping tokens from its
+/// end (or start, with `from_start`) while the cut splits a multi-byte
+/// character. Returns the text and the number of tokens it holds.
+fn decode_whole_chars(tokens: &[tiktoken_rs::Rank], from_start: bool) -> (String, usize) {
+    let mut tokens = tokens;
+    loop {
+        if let Ok(text) = get_tokenizer().decode(tokens.to_vec()) {
+            return (text, tokens.len());
+        }
+        tokens = match (from_start, tokens) {
+            (_, []) => return (String::new(), 0),
+            (true, [_, rest @ ..]) | (false, [rest @ .., _]) => rest,
+        };
+    }
+}
+
+/// Keep only the `count` largest (or smallest) files by content size.
+/// Ties are broken by path so the selection is deterministic.
+pub fn select_by_size(
+    mut files: Vec<ProcessedFile>,
+    mode: &str,
+    count: usize,
+) -> Vec<ProcessedFile> {
+    files.sort_by(|a, b| {
+        let by_size = if mode == "smallest" {
+            a.size_bytes.cmp(&b.size_bytes)
+        } else {
+            b.size_bytes.cmp(&a.size_bytes)
+        };
+        by_size.then_with(|| a.rel_path.cmp(&b.rel_path))
+    });
+    files.truncate(count);
+    files
+}
+
+/// Keep the `count` files with the highest priority; ties go to the
+/// alphabetically first path so the result doesn't depend on walk order.
+pub fn keep_highest_priority(mut files: Vec<ProcessedFile>, count: usize) -> Vec<ProcessedFile> {
+    files.par_sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.rel_path.cmp(&b.rel_path))
+    });
+    files.truncate(count);
+    files
+}
+
+/// `max_total_size` as a number: tokens in token mode, otherwise bytes
+pub fn total_size_limit(config: &YekConfig) -> anyhow::Result<Option<usize>> {
+    let Some(limit) = &config.max_total_size else {
+        return Ok(None);
+    };
+    let parsed = if config.token_mode {
+        parse_token_limit(limit)
+            .map_err(|_| anyhow!("max_total_size: Invalid token size '{}'", limit))?
+    } else {
+        ByteSize::from_str(limit)
+            .map_err(|e| anyhow!("max_total_size: Invalid size format: {}", e))?
+            .as_u64() as usize
+    };
+    Ok(Some(parsed))
+}
+
+/// Keep the highest-priority files while their combined size (as counted
+/// against `max_size`) fits `budget`; the first file that doesn't fit and
+/// everything less important is dropped. Returns the kept files and the
+/// combined size of the dropped ones.
+pub fn keep_within_total_size(
+    files: Vec<ProcessedFile>,
+    budget: usize,
+    config: &YekConfig,
+) -> anyhow::Result<(Vec<ProcessedFile>, usize)> {
+    let count = files.len();
+    let mut files = keep_highest_priority(files, count);
+    let sizes = files
+        .iter()
+        .map(|file| entry_size(file, config))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut used = 0_usize;
+    let fits = sizes
+        .iter()
+        .take_while(|&&size| {
+            used = used.saturating_add(size);
+            used <= budget
+        })
+        .count();
+    let omitted = sizes[fits..].iter().sum();
+
+    files.truncate(fits);
+    Ok((files, omitted))
+}
+
+/// Drop files whose content exactly matches an earlier file's, recording
+/// their paths as aliases of that earlier file. Empty files are left alone.
+pub fn merge_identical_files(files: Vec<ProcessedFile>) -> Vec<ProcessedFile> {
+    let mut original_of = vec![None; files.len()];
+    let mut first_with_content: HashMap<&str, usize> = HashMap::new();
+    for (index, file) in files.iter().enumerate() {
+        if file.content.is_empty() {
+            continue;
+        }
+        let first = *first_with_content
+            .entry(file.content.as_str())
+            .or_insert(index);
+        if first != index {
+            original_of[index] = Some(first);
+        }
+    }
+
+    let mut aliases = vec![Vec::new(); files.len()];
+    for (index, original) in original_of.iter().enumerate() {
+        if let Some(original) = original {
+            aliases[*original].push(files[index].rel_path.clone());
+        }
+    }
+
+    files
+        .into_iter()
+        .zip(original_of)
+        .zip(aliases)
+        .filter_map(|((mut file, original), aliases)| {
+            original.is_none().then(|| {
+                file.aliases = aliases;
+                file
+            })
+        })
+        .collect()
+}
+
+/// Number of hash functions in each MinHash signature.
+const MINHASH_PERMUTATIONS: u64 = 128;
+
+/// MinHash signature over the set of trimmed, non-empty lines of `content`.
+/// Returns `None` when there is nothing to compare.
+fn minhash_signature(content: &str) -> Option<Vec<u64>> {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let lines: HashSet<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let signature = (0..MINHASH_PERMUTATIONS)
+        .map(|seed| {
+            lines
+                .iter()
+                .map(|line| {
+                    let mut hasher = DefaultHasher::new();
+                    seed.hash(&mut hasher);
+                    line.hash(&mut hasher);
+                    hasher.finish()
+                })
+                .min()
+                .unwrap_or(u64::MAX)
+        })
+        .collect();
+    Some(signature)
+}
+
+/// Estimated Jaccard similarity of two MinHash signatures.
+fn minhash_similarity(a: &[u64], b: &[u64]) -> f64 {
+    let matching = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    matching as f64 / a.len() as f64
+}
+
+/// Drop files whose line-set similarity to an already kept file is at least
+/// `threshold`. Files are visited highest priority first, so each cluster of
+/// near-duplicates is represented by its most important member.
+pub fn remove_near_duplicates(mut files: Vec<ProcessedFile>, threshold: f64) -> Vec<ProcessedFile> {
+    files.sort_by(|a, b| {
+        b.priority
+            .cmp(&a.priority)
+            .then_with(|| a.rel_path.cmp(&b.rel_path))
+    });
+
+    let signatures: Vec<Option<Vec<u64>>> = files
+        .par_iter()
+        .map(|file| minhash_signature(&file.content))
+        .collect();
+
+    let mut kept_signatures: Vec<&Vec<u64>> = Vec::new();
+    let mut keep = vec![true; files.len()];
+    for (idx, signature) in signatures.iter().enumerate() {
+        let Some(signature) = signature else {
+            continue;
+        };
+        if kept_signatures
+            .iter()
+            .any(|kept| minhash_similarity(kept, signature) >= threshold)
+        {
+            tracing::debug!("Skipping near-duplicate file: {}", files[idx].rel_path);
+            keep[idx] = false;
+        } else {
+            kept_signatures.push(signature);
+        }
+    }
+
+    let mut keep = keep.into_iter();
+    files.retain(|_| keep.next().unwrap_or(true));
+    files
+}
+
 pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
     // Generate tree header if requested
     let tree_header = if config.tree_header || config.tree_only {
@@ -157,13 +957,7 @@ pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Resu
     }
 
     let mut accumulated = 0_usize;
-    let cap = if config.token_mode {
-        parse_token_limit(&config.tokens)?
-    } else {
-        ByteSize::from_str(&config.max_size)
-            .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?
-            .as_u64() as usize
-    };
+    let cap = size_cap(config)?;
 
     // Account for tree header size in capacity calculations
     let tree_header_size = if config.tree_header {
@@ -178,43 +972,34 @@ pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Resu
 
     accumulated += tree_header_size;
 
-    // Sort by priority (asc) and file_index (asc)
-    let mut sorted_files: Vec<_> = files.iter().collect();
-    sorted_files.sort_by(|a, b| {
-        a.priority
-            .cmp(&b.priority)
-            .then_with(|| a.rel_path.cmp(&b.rel_path))
-    });
+    // The preamble is emitted once at the very top (not supported for JSON output)
+    let preamble = match &config.preamble {
+        Some(template) if !config.json => render_preamble(template, config),
+        _ => String::new(),
+    };
+    accumulated += if config.token_mode {
+        count_tokens(&preamble)
+    } else {
+        preamble.len()
+    };
+
+    // Imports between the candidate files, listed before the content (not supported for JSON output)
+    let dep_graph = if config.dep_graph && !config.json {
+        depgraph::render_dependency_graph(&depgraph::dependency_graph(files))
+    } else {
+        String::new()
+    };
+    accumulated += if config.token_mode {
+        count_tokens(&dep_graph)
+    } else {
+        dep_graph.len()
+    };
 
     let mut files_to_include = Vec::new();
-    for file in sorted_files {
-        let content_size = if config.token_mode {
-            // Format the file content with template first, then count tokens
-            let content = format_content_with_line_numbers(&file.content, config.line_numbers);
-            let formatted = if config.json {
-                serde_json::to_string(&serde_json::json!({
-                    "filename": &file.rel_path,
-                    "content": content,
-                }))
-                .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
-            } else {
-                config
-                    .output_template
-                    .as_ref()
-                    .expect("output_template should be set")
-                    .replace("FILE_PATH", &file.rel_path)
-                    .replace("FILE_CONTENT", &content)
-                    // Handle both literal "\n" and escaped "\\n"
-                    .replace("\\\\\n", "\n") // First handle escaped newline
-                    .replace("\\\\n", "\n") // Then handle escaped \n sequence
-            };
-            count_tokens(&formatted)
-        } else {
-            let content = format_content_with_line_numbers(&file.content, config.line_numbers);
-            content.len()
-        };
+    for file in budget_order(files, config) {
+        let content_size = entry_size(file, config)?;
 
-        if accumulated + content_size <= cap {
+        if accumulated.saturating_add(content_size) <= cap {
             accumulated += content_size;
             files_to_include.push(file);
         } else {
@@ -222,6 +1007,11 @@ pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Resu
         }
     }
 
+    // Optionally surface each directory's README right before its code
+    if config.docs_first_per_dir {
+        files_to_include = group_readmes_with_dirs(files_to_include);
+    }
+
     let main_content = if config.json {
         // JSON array of objects
         serde_json::to_string_pretty(
@@ -229,39 +1019,650 @@ pub fn concat_files(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Resu
                 .iter()
                 .map(|f| {
                     let content = format_content_with_line_numbers(&f.content, config.line_numbers);
-                    serde_json::json!({
+                    let mut entry = serde_json::json!({
                         "filename": &f.rel_path,
                         "content": content,
-                    })
+                    });
+                    if !f.aliases.is_empty() {
+                        entry["aliases"] = serde_json::json!(&f.aliases);
+                    }
+                    entry
                 })
                 .collect::<Vec<_>>(),
         )?
     } else {
-        // Use the user-defined template
-        files_to_include
+        let template = config
+            .output_template
+            .as_ref()
+            .expect("output_template should be set");
+        let (shared_headers, contents) = if config.dedupe_boilerplate {
+            dedupe_boilerplate(&files_to_include)
+        } else {
+            (
+                Vec::new(),
+                files_to_include
+                    .iter()
+                    .map(|f| Cow::Borrowed(f.content.as_str()))
+                    .collect(),
+            )
+        };
+
+        // Each file through the user-defined template
+        let entries: Vec<String> = files_to_include
             .iter()
-            .map(|f| {
-                let content = format_content_with_line_numbers(&f.content, config.line_numbers);
-                config
-                    .output_template
-                    .as_ref()
-                    .expect("output_template should be set")
-                    .replace("FILE_PATH", &f.rel_path)
-                    .replace("FILE_CONTENT", &content)
-                    // Handle both literal "\n" and escaped "\\n"
-                    .replace("\\\\\n", "\n") // First handle escaped newline
-                    .replace("\\\\n", "\n") // Then handle escaped \n sequence
+            .zip(&contents)
+            .map(|(f, content)| {
+                let rendered = render_content(content, &f.rel_path, config);
+                render_file_entry(
+                    template,
+                    &f.rel_path,
+                    &f.aliases,
+                    content,
+                    &rendered,
+                    f.priority,
+                )
+            })
+            .collect();
+        let entries = if config.module_sections {
+            module_sections(&files_to_include, entries)
+        } else {
+            entries
+        };
+
+        // Shared headers come first
+        shared_headers
+            .iter()
+            .enumerate()
+            .map(|(i, header)| {
+                render_file_entry(template, &shared_header_label(i), &[], header, header, 0)
             })
+            .chain(entries)
             .collect::<Vec<_>>()
             .join("\n")
     };
 
-    // Combine tree header with main content
+    // Combine preamble, tree header and dependency graph with main content
     if config.tree_header {
-        Ok(format!("{}{}", tree_header, main_content))
+        Ok(format!(
+            "{}{}{}{}",
+            preamble, tree_header, dep_graph, main_content
+        ))
+    } else {
+        Ok(format!("{}{}{}", preamble, dep_graph, main_content))
+    }
+}
+
+/// Output size limit: `tokens` in token mode, otherwise `max_size` in bytes.
+/// Unbounded with `single_file`.
+fn size_cap(config: &YekConfig) -> anyhow::Result<usize> {
+    if config.single_file {
+        Ok(usize::MAX)
+    } else if config.token_mode {
+        parse_token_limit(&config.tokens)
+    } else {
+        Ok(ByteSize::from_str(&config.max_size)
+            .map_err(|e| anyhow!("max_size: Invalid size format: {}", e))?
+            .as_u64() as usize)
+    }
+}
+
+/// Files in the order they are budgeted against the size limit: output
+/// order, with manifests first when `manifests_first` is set.
+fn budget_order<'a>(files: &'a [ProcessedFile], config: &YekConfig) -> Vec<&'a ProcessedFile> {
+    // Sort by priority (asc) and path (asc)
+    let mut sorted_files: Vec<_> = files.iter().collect();
+    sorted_files.sort_by(|a, b| output_order(a, b, config));
+
+    // Manifests lead the output (and are budgeted first), whatever their priority
+    if config.manifests_first {
+        sorted_files.sort_by_key(|f| !is_manifest(&f.rel_path));
+    }
+    sorted_files
+}
+
+/// Size a file counts against the limit: rendered tokens in token mode,
+/// otherwise content bytes.
+fn entry_size(file: &ProcessedFile, config: &YekConfig) -> anyhow::Result<usize> {
+    let content = if config.json {
+        format_content_with_line_numbers(&file.content, config.line_numbers)
+    } else {
+        render_content(&file.content, &file.rel_path, config)
+    };
+    if !config.token_mode {
+        return Ok(content.len());
+    }
+
+    // Format the file content with template first, then count tokens
+    let formatted = if config.json {
+        serde_json::to_string(&serde_json::json!({
+            "filename": &file.rel_path,
+            "content": content,
+        }))
+        .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?
     } else {
-        Ok(main_content)
+        render_file_entry(
+            config
+                .output_template
+                .as_ref()
+                .expect("output_template should be set"),
+            &file.rel_path,
+            &file.aliases,
+            &file.content,
+            &content,
+            file.priority,
+        )
+    };
+    Ok(count_tokens(&formatted))
+}
+
+/// Order files are emitted in: by priority, ascending (most important last)
+/// unless `priority_order` is "descending", then by path. With `no_sort`, path
+/// alone, so priorities and git boosts are ignored.
+fn output_order(a: &ProcessedFile, b: &ProcessedFile, config: &YekConfig) -> std::cmp::Ordering {
+    if config.no_sort {
+        return a.rel_path.cmp(&b.rel_path);
+    }
+    let by_priority = if config.priority_order == "descending" {
+        b.priority.cmp(&a.priority)
+    } else {
+        a.priority.cmp(&b.priority)
+    };
+    by_priority.then_with(|| a.rel_path.cmp(&b.rel_path))
+}
+
+/// Render one file through an output template. FILE_FENCE expands to a run
+/// of backticks longer than any in the content, so fenced blocks never close
+/// early. FILE_LINES, FILE_BYTES, FILE_LANG and FILE_PRIORITY describe the
+/// file; they are filled in before FILE_CONTENT so content is never rewritten.
+/// `raw` is the file as read, which the counts describe, and `content` is
+/// what FILE_CONTENT expands to, with any line numbers or fence added.
+/// Aliases follow the path as `a.txt (also: b.txt, c.txt)`.
+fn render_file_entry(
+    template: &str,
+    rel_path: &str,
+    aliases: &[String],
+    raw: &str,
+    content: &str,
+    priority: i32,
+) -> String {
+    let mut rendered = if aliases.is_empty() {
+        template.replace("FILE_PATH", rel_path)
+    } else {
+        template.replace(
+            "FILE_PATH",
+            &format!("{} (also: {})", rel_path, aliases.join(", ")),
+        )
+    };
+    if rendered.contains("FILE_FENCE") {
+        rendered = rendered.replace("FILE_FENCE", &code_fence_for(raw));
+    }
+    if rendered.contains("FILE_LINES") {
+        rendered = rendered.replace("FILE_LINES", &raw.lines().count().to_string());
+    }
+    if rendered.contains("FILE_LANG") {
+        rendered = rendered.replace("FILE_LANG", &detect_language(rel_path));
     }
+    rendered = rendered
+        .replace("FILE_BYTES", &raw.len().to_string())
+        .replace("FILE_PRIORITY", &priority.to_string());
+    rendered
+        .replace("FILE_CONTENT", content)
+        // Handle both literal "\n" and escaped "\\n"
+        .replace("\\\\\n", "\n") // First handle escaped newline
+        .replace("\\\\n", "\n") // Then handle escaped \n sequence
+}
+
+/// Shortest backtick fence (at least three) that is longer than every run of
+/// backticks in `content`, as CommonMark requires
+pub fn code_fence_for(content: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat((longest_run + 1).max(3))
+}
+
+/// Top-level directory of a relative path, or "." for files at the root
+fn top_level_module(rel_path: &str) -> String {
+    let mut components = Path::new(rel_path).components();
+    match (components.next(), components.next()) {
+        (Some(first), Some(_)) => first.as_os_str().to_string_lossy().into_owned(),
+        _ => ".".to_string(),
+    }
+}
+
+/// Group rendered file entries by top-level directory, in order of first
+/// appearance, and put a tree of each directory at the start of its section.
+fn module_sections(files: &[&ProcessedFile], entries: Vec<String>) -> Vec<String> {
+    let mut modules: Vec<(String, Vec<usize>)> = Vec::new();
+    for (index, file) in files.iter().enumerate() {
+        let module = top_level_module(&file.rel_path);
+        match modules.iter_mut().find(|(name, _)| *name == module) {
+            Some((_, members)) => members.push(index),
+            None => modules.push((module, vec![index])),
+        }
+    }
+
+    let mut entries: Vec<Option<String>> = entries.into_iter().map(Some).collect();
+    let mut sections = Vec::with_capacity(entries.len() + modules.len());
+    for (module, members) in modules {
+        let paths: Vec<std::path::PathBuf> = members
+            .iter()
+            .map(|&i| std::path::PathBuf::from(&files[i].rel_path))
+            .collect();
+        sections.push(format!("Module: {}\n{}", module, generate_tree(&paths)));
+        sections.extend(members.iter().filter_map(|&i| entries[i].take()));
+    }
+    sections
+}
+
+fn shared_header_label(index: usize) -> String {
+    format!("[shared header {}]", index + 1)
+}
+
+/// A file's leading block: everything before its first blank line
+fn leading_block(content: &str) -> &str {
+    content.find("\n\n").map_or("", |end| &content[..end])
+}
+
+/// Replace leading blocks (license headers, banners, ...) that several files
+/// share with a reference to one shared copy. Returns the shared headers and
+/// each file's content, in input order.
+fn dedupe_boilerplate<'a>(files: &[&'a ProcessedFile]) -> (Vec<&'a str>, Vec<Cow<'a, str>>) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for file in files {
+        let block = leading_block(&file.content);
+        if block.lines().count() >= BOILERPLATE_MIN_LINES {
+            *counts.entry(block).or_default() += 1;
+        }
+    }
+
+    let mut headers: Vec<&str> = Vec::new();
+    let contents = files
+        .iter()
+        .map(|file| {
+            let block = leading_block(&file.content);
+            if counts.get(block).copied().unwrap_or(0) < 2 {
+                return Cow::Borrowed(file.content.as_str());
+            }
+            let index = headers
+                .iter()
+                .position(|header| *header == block)
+                .unwrap_or_else(|| {
+                    headers.push(block);
+                    headers.len() - 1
+                });
+            Cow::Owned(format!(
+                "{}{}",
+                shared_header_label(index),
+                &file.content[block.len()..]
+            ))
+        })
+        .collect();
+    (headers, contents)
+}
+
+/// True for recognized build/package manifests such as `Cargo.toml`
+fn is_manifest(rel_path: &str) -> bool {
+    Path::new(rel_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| MANIFEST_FILES.contains(&name))
+}
+
+/// True for README files (`README`, `README.md`, `readme.rst`, ...)
+fn is_readme(rel_path: &str) -> bool {
+    Path::new(rel_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.to_ascii_lowercase().starts_with("readme"))
+}
+
+/// Move each directory's README to just before the first other file of that
+/// same directory. Files without a README keep their relative order.
+fn group_readmes_with_dirs(files: Vec<&ProcessedFile>) -> Vec<&ProcessedFile> {
+    let parent_of = |file: &ProcessedFile| {
+        Path::new(&file.rel_path)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    };
+
+    let mut readmes: HashMap<std::path::PathBuf, Vec<&ProcessedFile>> = HashMap::new();
+    for file in files.iter().filter(|f| is_readme(&f.rel_path)) {
+        readmes.entry(parent_of(file)).or_default().push(file);
+    }
+
+    let mut ordered = Vec::with_capacity(files.len());
+    for file in files {
+        let dir = parent_of(file);
+        if is_readme(&file.rel_path) {
+            // Already emitted ahead of an earlier file from this directory
+            if !readmes.contains_key(&dir) {
+                continue;
+            }
+        }
+        if let Some(dir_readmes) = readmes.remove(&dir) {
+            ordered.extend(dir_readmes);
+            if is_readme(&file.rel_path) {
+                continue;
+            }
+        }
+        ordered.push(file);
+    }
+    ordered
+}
+
+/// Expand the placeholders of a preamble template.
+/// Supported placeholders: {repo}, {timestamp}, {version} and {command}.
+/// {command} comes from `config.command_line` and is empty when that is unset.
+pub fn render_preamble(template: &str, config: &YekConfig) -> String {
+    let repo = config
+        .input_paths
+        .first()
+        .and_then(|p| Path::new(p).canonicalize().ok())
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut rendered = template
+        .replace("{repo}", &repo)
+        .replace("{timestamp}", &timestamp.to_string())
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+        .replace(
+            "{command}",
+            config.command_line.as_deref().unwrap_or_default(),
+        )
+        .replace("\\n", "\n");
+    if !rendered.ends_with('\n') {
+        rendered.push('\n');
+    }
+    rendered
+}
+
+/// Render files in one of the supported output formats ("text", "markdown" or "json").
+/// Used when several formats are requested in a single run so the walk is only done once.
+pub fn concat_files_for_format(
+    files: &[ProcessedFile],
+    config: &YekConfig,
+    format: &str,
+) -> anyhow::Result<String> {
+    let mut format_config = config.clone();
+    match format {
+        "text" => format_config.json = false,
+        "markdown" => {
+            format_config.json = false;
+            format_config.output_template = Some(MARKDOWN_OUTPUT_TEMPLATE.to_string());
+        }
+        "json" => {
+            format_config.json = true;
+            format_config.tree_header = false;
+            format_config.tree_only = false;
+        }
+        "embeddings" => return embeddings_jsonl(files, config),
+        other => return Err(anyhow!("formats: Unknown output format '{}'", other)),
+    }
+    concat_files(files, &format_config)
+}
+
+/// One JSON object per line and per file, shaped for embedding pipelines:
+/// `{"id": path, "text": content, "metadata": {priority, size, language}}`.
+pub fn embeddings_jsonl(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<String> {
+    let mut sorted_files: Vec<_> = files.iter().collect();
+    sorted_files.sort_by(|a, b| output_order(a, b, config));
+
+    let mut output = String::new();
+    for file in sorted_files {
+        let line = serde_json::to_string(&serde_json::json!({
+            "id": &file.rel_path,
+            "text": format_content_with_line_numbers(&file.content, config.line_numbers),
+            "metadata": {
+                "priority": file.priority,
+                "size": file.size_bytes,
+                "language": language_for_path(&file.rel_path),
+            },
+        }))
+        .map_err(|e| anyhow!("Failed to serialize JSON: {}", e))?;
+        output.push_str(&line);
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Best-effort language name from a file's name or extension
+fn language_for_path(path: &str) -> String {
+    match detect_language(path) {
+        language if language.is_empty() => "text".to_string(),
+        language => language,
+    }
+}
+
+/// File extension used for the output file of a given format
+pub fn format_extension(format: &str) -> &'static str {
+    match format {
+        "markdown" => "md",
+        "json" => "json",
+        "embeddings" => "jsonl",
+        _ => "txt",
+    }
+}
+
+/// `path` with the file extension of `compress` appended, e.g. `out.txt.gz`
+pub fn compressed_path(path: &Path, compress: Option<&str>) -> PathBuf {
+    match compress {
+        Some("gzip") => PathBuf::from(format!("{}.gz", path.display())),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// `data` compressed with `compress` ("gzip"), or unchanged when it's unset
+pub fn compress_output(data: &[u8], compress: Option<&str>) -> Result<Vec<u8>> {
+    match compress {
+        None => Ok(data.to_vec()),
+        Some("gzip") => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data)?;
+            Ok(encoder.finish()?)
+        }
+        Some(other) => Err(anyhow!("compress: unsupported format '{}'", other)),
+    }
+}
+
+/// Write `output` to `path`, compressed per `config.compress`. Returns the
+/// path actually written, which carries the compression extension.
+pub fn write_output_file(path: &Path, output: &str, config: &YekConfig) -> Result<PathBuf> {
+    let compress = config.compress.as_deref();
+    let path = compressed_path(path, compress);
+    std::fs::write(&path, compress_output(output.as_bytes(), compress)?)?;
+    Ok(path)
+}
+
+/// Result of the `plan` estimate pass: how many packs the repository needs to
+/// fit a model's context window and the per-pack token budget that balances them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackPlan {
+    pub model: String,
+    pub context_window: usize,
+    pub reserved_tokens: usize,
+    pub total_tokens: usize,
+    pub packs: usize,
+    pub suggested_tokens: usize,
+}
+
+impl std::fmt::Display for PackPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Model: {} ({} token context, {} reserved)",
+            self.model, self.context_window, self.reserved_tokens
+        )?;
+        writeln!(f, "Estimated tokens: {}", self.total_tokens)?;
+        writeln!(f, "Packs needed: {}", self.packs)?;
+        write!(f, "Suggested: --tokens {}", self.suggested_tokens)
+    }
+}
+
+/// What a run would pack, as reported by `--dry-run`: every candidate file in
+/// budget order and whether it fits under the size limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunReport {
+    pub entries: Vec<DryRunEntry>,
+    /// "tokens" in token mode, otherwise "bytes"
+    pub unit: &'static str,
+    pub cap: usize,
+    /// How byte sizes are shown, as in `YekConfig::size_display`
+    pub size_display: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DryRunEntry {
+    pub rel_path: String,
+    pub priority: i32,
+    /// Size counted against the limit, in the report's unit
+    pub size: usize,
+    /// False once the limit is reached; this and later files would be left out
+    pub included: bool,
+}
+
+impl std::fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = |amount: usize| format_budget(amount, self.unit == "tokens", &self.size_display);
+        writeln!(f, "{:>8} {:>12}  PATH", "PRIORITY", "SIZE")?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:>8} {:>12}  {}{}",
+                entry.priority,
+                size(entry.size),
+                entry.rel_path,
+                if entry.included { "" } else { " (over limit)" }
+            )?;
+        }
+        let included: Vec<_> = self.entries.iter().filter(|e| e.included).collect();
+        write!(
+            f,
+            "{} of {} files would be packed ({} of {})",
+            included.len(),
+            self.entries.len(),
+            size(included.iter().map(|e| e.size).sum::<usize>()),
+            size(self.cap)
+        )
+    }
+}
+
+/// Work out which files a run would pack, and their sizes, without rendering
+/// the output. Headers such as the tree and preamble are not counted.
+pub fn dry_run(files: &[ProcessedFile], config: &YekConfig) -> anyhow::Result<DryRunReport> {
+    let cap = size_cap(config)?;
+    let mut accumulated = 0_usize;
+    let mut over_limit = false;
+    let entries = budget_order(files, config)
+        .into_iter()
+        .map(|file| {
+            let size = entry_size(file, config)?;
+            over_limit = over_limit || accumulated.saturating_add(size) > cap;
+            if !over_limit {
+                accumulated += size;
+            }
+            Ok(DryRunEntry {
+                rel_path: file.rel_path.clone(),
+                priority: file.priority,
+                size,
+                included: !over_limit,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(DryRunReport {
+        entries,
+        unit: if config.token_mode { "tokens" } else { "bytes" },
+        cap,
+        size_display: config.size_display.clone(),
+    })
+}
+
+/// Estimate the tokens of all files as they would be rendered, ignoring any size cap
+pub fn estimate_total_tokens(files: &[ProcessedFile], config: &YekConfig) -> usize {
+    let template = config
+        .output_template
+        .as_deref()
+        .unwrap_or(defaults::DEFAULT_OUTPUT_TEMPLATE);
+    files
+        .par_iter()
+        .map(|file| {
+            let content = render_content(&file.content, &file.rel_path, config);
+            count_tokens(&render_file_entry(
+                template,
+                &file.rel_path,
+                &file.aliases,
+                &file.content,
+                &content,
+                file.priority,
+            ))
+        })
+        .sum()
+}
+
+/// Estimate the tokens of every file as it would be rendered and work out how
+/// many packs are needed for `model`, leaving `reserved_tokens` for the prompt.
+pub fn plan_packing(
+    files: &[ProcessedFile],
+    config: &YekConfig,
+    model: &str,
+) -> anyhow::Result<PackPlan> {
+    let context_window = defaults::MODEL_CONTEXT_WINDOWS
+        .iter()
+        .find(|(name, _)| *name == model)
+        .map(|(_, window)| *window)
+        .ok_or_else(|| anyhow!("plan: Unknown model '{}'", model))?;
+    let budget = context_window
+        .checked_sub(config.reserved_tokens)
+        .filter(|budget| *budget > 0)
+        .ok_or_else(|| anyhow!("reserved_tokens: exceeds the context window of {}", model))?;
+
+    let total_tokens = estimate_total_tokens(files, config);
+    let packs = total_tokens.div_ceil(budget).max(1);
+    Ok(PackPlan {
+        model: model.to_string(),
+        context_window,
+        reserved_tokens: config.reserved_tokens,
+        total_tokens,
+        packs,
+        suggested_tokens: total_tokens.div_ceil(packs).max(1),
+    })
+}
+
+/// File content as it goes into a text template: with line numbers and a
+/// code fence when requested. A template that places FILE_FENCE itself
+/// already fences the content, so `fence_code` is skipped for it.
+fn render_content(content: &str, rel_path: &str, config: &YekConfig) -> String {
+    let content = format_content_with_line_numbers(content, config.line_numbers);
+    let template_fences = config
+        .output_template
+        .as_deref()
+        .is_some_and(|template| template.contains("FILE_FENCE"));
+    if config.fence_code && !template_fences {
+        fence_content(&content, rel_path)
+    } else {
+        content
+    }
+}
+
+/// Wrap content in a backtick fence tagged with the file's language, e.g.
+/// ```` ```rust ````. The fence is longer than any backtick run in the content.
+fn fence_content(content: &str, rel_path: &str) -> String {
+    let fence = code_fence_for(content);
+    let newline = if content.is_empty() || content.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    format!(
+        "{}{}\n{}{}{}",
+        fence,
+        detect_language(rel_path),
+        content,
+        newline,
+        fence
+    )
 }
 
 /// Format file content with line numbers if requested
@@ -315,3 +1716,41 @@ pub fn parse_token_limit(limit: &str) -> anyhow::Result<usize> {
 pub fn count_tokens(text: &str) -> usize {
     get_tokenizer().encode_with_special_tokens(text).len()
 }
+
+/// Size of `content` in the units picked by `size_display`: "bytes" (e.g.
+/// "1536 bytes"), "human" (e.g. "1.5 KB") or "tokens" (e.g. "312 tokens")
+pub fn format_size(content: &str, size_display: &str) -> String {
+    match size_display {
+        "tokens" => format!("{} tokens", count_tokens(content)),
+        _ => format_bytes(content.len() as u64, size_display),
+    }
+}
+
+/// A byte count in the units picked by `size_display`. There is no content
+/// to count tokens in, so "tokens" shows it like "human".
+pub fn format_bytes(bytes: u64, size_display: &str) -> String {
+    if size_display == "bytes" {
+        return format!("{} bytes", bytes);
+    }
+    const UNITS: &[&str] = &["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// An amount counted against the size limits: tokens in token mode,
+/// otherwise bytes shown per `size_display`
+fn format_budget(amount: usize, token_mode: bool, size_display: &str) -> String {
+    if token_mode {
+        format!("{} tokens", amount)
+    } else {
+        format_bytes(amount as u64, size_display)
+    }
+}
@@ -1,28 +1,39 @@
 use anyhow::Result;
 use bytesize::ByteSize;
 use rayon::join;
+use std::io::{IsTerminal, Write};
 use std::path::Path;
-use tracing::{debug, Level};
+use std::str::FromStr;
+use tracing::{debug, warn, Level};
 use tracing_subscriber::fmt;
-use yek::{config::YekConfig, serialize_repo};
+use yek::{
+    compress_output, compressed_path, concat_files_for_format,
+    config::YekConfig,
+    count_includable_files, dry_run, estimate_total_tokens, format_bytes, format_extension,
+    format_size, plan_packing,
+    progress::{progress_bar_callback, stderr_progress_bar, ProgressEvent},
+    run_post_write_command, serialize_repo, serialize_repo_with_progress, write_output_file,
+};
 
 fn main() -> Result<()> {
     // 1) Parse CLI + config files:
     let mut full_config = YekConfig::init_config();
+    full_config.command_line = Some(std::env::args().collect::<Vec<_>>().join(" "));
 
-    let env_filter = if full_config.debug {
-        "yek=debug,ignore=off"
+    // Quiet keeps errors only; diagnostics go to stderr so they never mix with streamed output
+    let (level, env_filter) = if full_config.debug {
+        (Level::DEBUG, "yek=debug,ignore=off")
+    } else if full_config.quiet {
+        (Level::ERROR, "yek=error,ignore=off")
     } else {
-        "yek=info,ignore=off"
+        (Level::INFO, "yek=info,ignore=off")
     };
 
     // 2) Initialize tracing:
     fmt::Subscriber::builder()
-        .with_max_level(if full_config.debug {
-            Level::DEBUG
-        } else {
-            Level::INFO
-        })
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
         .with_target(false)
         .with_thread_ids(false)
         .with_thread_names(false)
@@ -33,15 +44,92 @@ fn main() -> Result<()> {
         .compact()
         .init();
 
+    for warning in &full_config.warnings {
+        warn!("{}", warning);
+    }
+
+    if full_config.show_config_source {
+        eprintln!("Config source: {}", full_config.config_source());
+    }
+
     if full_config.debug {
+        debug!("Config source: {}", full_config.config_source());
         let config_str = serde_json::to_string_pretty(&full_config)?;
         debug!("Configuration:\n{}", config_str);
     }
 
+    // Counting only walks the inputs; nothing is read or written
+    if full_config.count_only {
+        println!("{}", count_includable_files(&full_config)?);
+        return Ok(());
+    }
+
+    // A dry run reports what would be packed; nothing is written
+    if full_config.dry_run {
+        let (_, files) = serialize_repo(&full_config)?;
+        println!("{}", dry_run(&files, &full_config)?);
+        return Ok(());
+    }
+
+    // Planning only estimates; nothing is written
+    if let Some(model) = &full_config.plan {
+        let (_, files) = serialize_repo(&full_config)?;
+        println!("{}", plan_packing(&files, &full_config, model)?);
+        return Ok(());
+    }
+
+    // Token budget gate (e.g. for CI): estimate only, fail when over budget
+    if let Some(budget) = full_config.fail_over_tokens {
+        let (_, files) = serialize_repo(&full_config)?;
+        let total_tokens = estimate_total_tokens(&files, &full_config);
+        if total_tokens > budget {
+            eprintln!(
+                "Error: estimated {} tokens exceeds the budget of {} tokens",
+                total_tokens, budget
+            );
+            std::process::exit(1);
+        }
+        println!(
+            "Estimated {} tokens, within the budget of {} tokens",
+            total_tokens, budget
+        );
+        return Ok(());
+    }
+
+    // Hold the output_dir lock for the rest of the run whenever we write into it
+    #[cfg(feature = "lock")]
+    let _output_lock = match &full_config.output_dir {
+        Some(output_dir) if !full_config.stream || full_config.output_name.is_some() => {
+            Some(yek::lock_output_dir(output_dir)?)
+        }
+        _ => None,
+    };
+
+    // The bar draws on stderr, so it never corrupts output streamed to stdout
+    let on_progress = full_config
+        .progress
+        .then(|| progress_bar_callback(stderr_progress_bar()));
+    let serialize = |config: &YekConfig| match &on_progress {
+        Some(progress) => serialize_repo_with_progress(config, progress.clone()),
+        None => serialize_repo(config),
+    };
+    let report_written = |path: &Path| {
+        if let Some(progress) = &on_progress {
+            progress(ProgressEvent::OutputWritten {
+                path: path.to_path_buf(),
+            });
+        }
+    };
+
     // If streaming => skip checksum + read. Just do single-thread call to serialize_repo.
     // If not streaming => run checksum + repo serialization in parallel.
     if full_config.stream {
-        let (output, files) = serialize_repo(&full_config)?;
+        let (output, files) = serialize(&full_config)?;
+        // Only a single format can be streamed, so use the first requested one
+        let output = match full_config.formats.first() {
+            Some(format) => concat_files_for_format(&files, &full_config, format)?,
+            None => output,
+        };
         // If output_name provided, write to file, else print to stdout:
         if let Some(output_name) = &full_config.output_name {
             let final_output_path = if let Some(output_dir) = &full_config.output_dir {
@@ -54,20 +142,42 @@ fn main() -> Result<()> {
                 // Only output_name provided - use it directly
                 output_name.clone()
             };
-            std::fs::write(&final_output_path, output.as_bytes())?;
-            println!("{}", final_output_path);
+            let written = write_output_file(Path::new(&final_output_path), &output, &full_config)?;
+            report_written(&written);
+            post_write(&full_config, &written)?;
+            println!("{}", written.display());
         } else {
-            println!("{}", output);
+            match stream_truncation(&full_config)? {
+                Some(limit) if output.len() > limit => {
+                    // Cut at a char boundary so the streamed prefix stays valid UTF-8
+                    let cut = (0..=limit)
+                        .rev()
+                        .find(|&i| output.is_char_boundary(i))
+                        .unwrap_or(0);
+                    print_stream(&output[..cut], &full_config)?;
+                    warn!(
+                        "Output of {} exceeds truncate_stream_at of {}; truncated to {}",
+                        format_bytes(output.len() as u64, &full_config.size_display),
+                        format_bytes(limit as u64, &full_config.size_display),
+                        format_bytes(cut as u64, &full_config.size_display)
+                    );
+                }
+                _ => print_stream(&output, &full_config)?,
+            }
         }
 
         if full_config.debug {
             debug!("{} files processed (streaming).", files.len());
+            debug!(
+                "{} generated",
+                format_size(&output, &full_config.size_display)
+            );
             debug!("Output lines: {}", output.lines().count());
         }
     } else {
         // Not streaming => run repo serialization & checksum in parallel
         let (serialization_res, checksum_res) = join(
-            || serialize_repo(&full_config),
+            || serialize(&full_config),
             || YekConfig::get_checksum(&full_config.input_paths),
         );
 
@@ -75,6 +185,26 @@ fn main() -> Result<()> {
         let (output_string, files) = serialization_res?;
         let checksum = checksum_res;
 
+        // Multiple formats => write one file per format, reusing the processed files
+        if !full_config.formats.is_empty() {
+            let output_dir = full_config.output_dir.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("Output directory is required when writing multiple formats.")
+            })?;
+            for format in &full_config.formats {
+                let output = concat_files_for_format(&files, &full_config, format)?;
+                let path = Path::new(output_dir).join(format!(
+                    "yek-output-{}.{}",
+                    checksum,
+                    format_extension(format)
+                ));
+                let path = write_output_file(&path, &output, &full_config)?;
+                report_written(&path);
+                post_write(&full_config, &path)?;
+                println!("{}", path.display());
+            }
+            return Ok(());
+        }
+
         // Now set the final output file
         let final_path = if let Some(output_name) = &full_config.output_name {
             if let Some(output_dir) = &full_config.output_dir {
@@ -98,19 +228,28 @@ fn main() -> Result<()> {
                 .to_string_lossy()
                 .to_string()
         };
+        let final_path = compressed_path(Path::new(&final_path), full_config.compress.as_deref())
+            .to_string_lossy()
+            .to_string();
         full_config.output_file_full_path = Some(final_path.clone());
 
         // If debug, show stats
         if full_config.debug {
-            let size = ByteSize::b(output_string.len() as u64);
             debug!("{} files processed", files.len());
-            debug!("{} generated", size);
+            debug!(
+                "{} generated",
+                format_size(&output_string, &full_config.size_display)
+            );
             debug!("{} lines generated", output_string.lines().count());
         }
 
         // Actually write the final output file.
         // We'll do it right here (instead of inside `serialize_repo`) to ensure we use our new final_path:
-        std::fs::write(&final_path, output_string.as_bytes())?;
+        let output_bytes =
+            compress_output(output_string.as_bytes(), full_config.compress.as_deref())?;
+        std::fs::write(&final_path, output_bytes)?;
+        report_written(Path::new(&final_path));
+        post_write(&full_config, Path::new(&final_path))?;
 
         // Print path to stdout (like original code did)
         println!("{}", final_path);
@@ -118,3 +257,37 @@ fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Run the configured `post_write_command`, if any, on a written output file
+fn post_write(config: &YekConfig, path: &Path) -> Result<()> {
+    match &config.post_write_command {
+        Some(command) => run_post_write_command(command, path),
+        None => Ok(()),
+    }
+}
+
+/// Print streamed output to stdout, compressed as a whole when `compress` is set
+fn print_stream(output: &str, config: &YekConfig) -> Result<()> {
+    match config.compress.as_deref() {
+        Some(compress) => {
+            let bytes = compress_output(format!("{}\n", output).as_bytes(), Some(compress))?;
+            std::io::stdout().lock().write_all(&bytes)?;
+        }
+        None => println!("{}", output),
+    }
+    Ok(())
+}
+
+/// Number of bytes streamed output is cut to, if set. The output is built in
+/// full first, so this limits what is printed, not the work done.
+fn stream_truncation(config: &YekConfig) -> Result<Option<usize>> {
+    config
+        .truncate_stream_at
+        .as_deref()
+        .map(|limit| {
+            ByteSize::from_str(limit)
+                .map(|size| size.as_u64() as usize)
+                .map_err(|e| anyhow::anyhow!("truncate_stream_at: Invalid size format: {}", e))
+        })
+        .transpose()
+}
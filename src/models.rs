@@ -24,6 +24,9 @@ pub struct ProcessedFile {
     pub formatted_content: Option<String>,
     /// File category for improved sorting and organization
     pub category: FileCategory,
+    /// Other paths with identical content, emitted only through this file (see `dedup_identical`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
 }
 
 impl Clone for ProcessedFile {
@@ -37,6 +40,7 @@ impl Clone for ProcessedFile {
             token_count: OnceLock::new(),
             formatted_content: self.formatted_content.clone(),
             category: self.category,
+            aliases: self.aliases.clone(),
         }
     }
 }
@@ -55,6 +59,7 @@ impl ProcessedFile {
             token_count: OnceLock::new(),
             formatted_content: None,
             category,
+            aliases: Vec::new(),
         }
     }
 
@@ -76,6 +81,7 @@ impl ProcessedFile {
             token_count: OnceLock::new(),
             formatted_content: None,
             category,
+            aliases: Vec::new(),
         }
     }
 
@@ -224,6 +230,24 @@ pub struct InputConfig {
     pub max_git_depth: i32,
     /// Maximum git boost value
     pub git_boost_max: Option<i32>,
+    /// Git ref whose `.gitignore` files replace the working-tree ones
+    pub gitignore_ref: Option<String>,
+    /// Directories pruned from the walk, by name or by path relative to the input
+    pub exclude_dirs: Vec<String>,
+    /// Prune vendored dependency directories (see `VENDORED_DIRS`)
+    pub skip_vendored: bool,
+    /// Unignore patterns; a vendored directory they point into is still walked
+    pub unignore_patterns: Vec<String>,
+    /// When non-empty, only files matching one of these gitignore-style patterns are kept
+    pub include_patterns: Vec<String>,
+    /// Files whose detected language is one of these are skipped
+    pub exclude_languages: Vec<String>,
+    /// Honor the global gitignore (core.excludesFile) and .git/info/exclude
+    pub global_gitignore: bool,
+    /// Follow symlinks in the walk, packing each canonical target once
+    pub follow_symlinks: bool,
+    /// Canonical output directory, pruned from the walk when it lies inside an input directory
+    pub output_dir: Option<std::path::PathBuf>,
 }
 
 impl Default for InputConfig {
@@ -234,6 +258,15 @@ impl Default for InputConfig {
             binary_extensions: std::collections::HashSet::new(),
             max_git_depth: 100,
             git_boost_max: Some(100),
+            gitignore_ref: None,
+            exclude_dirs: Vec::new(),
+            skip_vendored: true,
+            unignore_patterns: Vec::new(),
+            include_patterns: Vec::new(),
+            exclude_languages: Vec::new(),
+            global_gitignore: true,
+            follow_symlinks: false,
+            output_dir: None,
         }
     }
 }
@@ -300,6 +333,28 @@ pub struct ProcessingConfig {
     pub memory_limit_mb: Option<usize>,
     /// Batch size for processing
     pub batch_size: usize,
+    /// Skip files whose decoded content has more than this ratio of replacement characters
+    pub max_replacement_ratio: f64,
+    /// Treat files starting with a known binary signature as binary
+    pub magic_detection: bool,
+    /// Files larger than this many bytes are skipped, or truncated with `truncate_oversize`
+    pub max_file_size: Option<u64>,
+    /// Truncate files over `max_file_size` instead of skipping them
+    pub truncate_oversize: bool,
+    /// How sizes in warnings are shown, as in `YekConfig::size_display`
+    pub size_display: String,
+    /// Bytes sampled from the start of a file for binary detection
+    pub binary_detection_bytes: usize,
+    /// Encoding for files that have no byte order mark and aren't valid UTF-8
+    pub fallback_encoding: Option<&'static encoding_rs::Encoding>,
+    /// Memory-map files of at least this many bytes (requires the `mmap` feature)
+    pub mmap_threshold: Option<u64>,
+    /// Externally computed priorities by relative path; these override all other sources
+    pub priority_scores: std::collections::HashMap<String, i32>,
+    /// Abort instead of warning when a file disappears between the walk and the read
+    pub fail_on_missing: bool,
+    /// Read files re-included by a `!pattern` as text, skipping binary detection
+    pub reincluded_as_text: bool,
 }
 
 impl Default for ProcessingConfig {
@@ -312,6 +367,17 @@ impl Default for ProcessingConfig {
             max_threads: None,
             memory_limit_mb: None,
             batch_size: 1000,
+            max_replacement_ratio: crate::defaults::DEFAULT_MAX_REPLACEMENT_RATIO,
+            magic_detection: true,
+            max_file_size: None,
+            truncate_oversize: false,
+            size_display: "human".to_string(),
+            binary_detection_bytes: crate::defaults::DEFAULT_BINARY_DETECTION_BYTES,
+            fallback_encoding: None,
+            mmap_threshold: None,
+            priority_scores: std::collections::HashMap::new(),
+            fail_on_missing: false,
+            reincluded_as_text: false,
         }
     }
 }
@@ -1,31 +1,83 @@
 use crate::{
+    cache::{content_hash, mtime_nanos, CacheEntry, FileCache},
+    defaults::{DEFAULT_IGNORE_PATTERNS, VENDORED_DIRS, YEKIGNORE_FILE},
+    language::is_language_excluded,
     models::{InputConfig, OutputConfig, ProcessedFile, ProcessingConfig},
     pipeline::ProcessingContext,
+    priority::DIRECTORY_PRIORITY_FILE,
+    progress::{ProgressCallback, ProgressEvent},
 };
 use anyhow::{anyhow, Result};
-use content_inspector::{inspect, ContentType};
-use ignore::gitignore::GitignoreBuilder;
+use bytesize::ByteSize;
+use encoding_rs::Encoding;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use path_slash::PathBufExt;
 use rayon::prelude::*;
 use std::{
-    collections::HashMap,
-    path::Path,
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
     sync::{Arc, Mutex},
     time::Instant,
 };
-use tracing::debug;
+use tracing::{debug, warn};
+
+/// Pooled read buffers larger than this are released after use so one huge
+/// file doesn't pin its allocation for the rest of the walk.
+const MAX_POOLED_BUFFER_BYTES: usize = 4 * 1024 * 1024;
+
+thread_local! {
+    static READ_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
 
 /// Thread-safe file processor that fixes race conditions
 pub struct ParallelFileProcessor {
     context: Arc<ProcessingContext>,
     file_counter: Arc<Mutex<HashMap<i32, usize>>>,
+    directory_priorities: Mutex<HashMap<PathBuf, Option<i32>>>,
+    include_matcher: Option<Gitignore>,
+    progress: Option<ProgressCallback>,
+    cache: Option<FileCache>,
 }
 
 impl ParallelFileProcessor {
     pub fn new(context: ProcessingContext) -> Self {
+        let include_matcher = build_include_matcher(&context.input_config.include_patterns);
         Self {
             context: Arc::new(context),
             file_counter: Arc::new(Mutex::new(HashMap::new())),
+            directory_priorities: Mutex::new(HashMap::new()),
+            include_matcher,
+            progress: None,
+            cache: None,
+        }
+    }
+
+    /// Report files discovered by the walk and each file processed to `progress`
+    pub fn with_progress(mut self, progress: Option<ProgressCallback>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Reuse earlier binary/text verdicts for files unchanged since `cache` was written
+    pub fn with_cache(mut self, cache: Option<FileCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Write the cache back to disk. A failure only costs the next run its
+    /// speedup, so it is logged instead of failing the run.
+    fn save_cache(&self) {
+        if let Some(Err(e)) = self.cache.as_ref().map(FileCache::save) {
+            warn!("Failed to save file cache: {e}");
+        }
+    }
+
+    fn report(&self, event: ProgressEvent) {
+        if let Some(progress) = &self.progress {
+            progress(event);
         }
     }
 
@@ -70,6 +122,98 @@ impl ParallelFileProcessor {
         Ok(all_processed_files)
     }
 
+    /// Like `process_files_parallel`, but yields files one at a time so only
+    /// one file's content is held in memory. The walk and the priorities are
+    /// computed up front; each file is read when the iterator reaches it.
+    /// Files come in `(priority, path)` order, and binary or vanished files are
+    /// skipped (a vanished file is an error with `fail_on_missing`).
+    pub fn process_files_stream(
+        self,
+        base_path: &Path,
+    ) -> Result<impl Iterator<Item = Result<ProcessedFile>>> {
+        let base_dir = self.determine_base_dir(base_path);
+        let mut pending: Vec<_> = self
+            .files_to_read(base_path, &base_dir)?
+            .into_par_iter()
+            .map(|(path, rel_path, force_text)| {
+                let (priority, category) = self.file_priority(&path, &rel_path, &base_dir);
+                (priority, rel_path, path, category, force_text)
+            })
+            .collect();
+        pending.par_sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+
+        let mut file_index = 0;
+        Ok(pending.into_iter().filter_map(
+            move |(priority, rel_path, path, category, force_text)| match self
+                .read_text(&path, &rel_path, force_text)
+            {
+                Ok(content) => {
+                    file_index += 1;
+                    Some(Ok(ProcessedFile::new_with_category(
+                        rel_path,
+                        content,
+                        priority,
+                        file_index - 1,
+                        category,
+                    )))
+                }
+                Err(_) if !self.context.file_system.path_exists(&path) => {
+                    self.handle_missing_file(&rel_path).err().map(Err)
+                }
+                Err(e) => {
+                    debug!("Skipping {rel_path}: {e}");
+                    None
+                }
+            },
+        ))
+    }
+
+    /// Count the files that pass the walk and ignore filters, without reading
+    /// them. Files only skipped later for binary content are still counted.
+    pub fn count_includable_files(&self, base_path: &Path) -> Result<usize> {
+        let base_dir = self.determine_base_dir(base_path);
+        Ok(self.files_to_read(base_path, &base_dir)?.len())
+    }
+
+    /// Every file under `base_path` that passes the walk and ignore filters,
+    /// as `(path, rel_path, force_text)`, where `force_text` marks files a
+    /// `!pattern` re-included that should skip binary detection.
+    fn files_to_read(
+        &self,
+        base_path: &Path,
+        base_dir: &Path,
+    ) -> Result<Vec<(PathBuf, String, bool)>> {
+        let reincluded_as_text = self.context.processing_config.reincluded_as_text;
+        let mut files = Vec::new();
+
+        for path in self.expand_globs(base_path)? {
+            if self.context.file_system.is_file(&path) {
+                let rel_path = self.normalize_path(&path, base_dir);
+                if !self.should_ignore_file(&path, &rel_path) {
+                    files.push((path, rel_path, false));
+                }
+            } else if self.context.file_system.is_directory(&path) {
+                let gitignore = self.build_gitignore(&path)?;
+                let historical_gitignores = match &self.context.input_config.gitignore_ref {
+                    Some(git_ref) => self.build_historical_gitignores(&path, git_ref)?,
+                    None => Vec::new(),
+                };
+                for (file_path, rel_path) in self.collect_files_to_process(
+                    &path,
+                    base_dir,
+                    &gitignore,
+                    &historical_gitignores,
+                )? {
+                    let force_text =
+                        reincluded_as_text && gitignore.matched(&file_path, false).is_whitelist();
+                    files.push((file_path, rel_path, force_text));
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
     /// Expand glob patterns into concrete paths
     fn expand_globs(&self, base_path: &Path) -> Result<Vec<std::path::PathBuf>> {
         let mut expanded_paths = Vec::new();
@@ -78,8 +222,11 @@ impl ParallelFileProcessor {
         for entry in glob::glob(&path_str)? {
             match entry {
                 Ok(path) => {
-                    // Resolve symlinks to prevent issues
-                    let resolved_path = if self.context.file_system.is_symlink(&path) {
+                    // Resolve symlinks to prevent issues. When following symlinks the
+                    // link's own path is kept so relative paths stay under the base.
+                    let resolved_path = if self.context.file_system.is_symlink(&path)
+                        && !self.context.input_config.follow_symlinks
+                    {
                         self.context
                             .file_system
                             .resolve_symlink(&path)
@@ -189,24 +336,43 @@ impl ParallelFileProcessor {
         }
 
         // Read and process file content
-        match self.context.file_system.read_file(file_path) {
-            Ok(content) => {
-                if inspect(&content) == ContentType::BINARY {
-                    debug!("Skipping binary file: {rel_path}");
-                    Ok(Vec::new())
-                } else {
-                    let processed_file = self.create_processed_file(&rel_path, &content)?;
-                    Ok(vec![processed_file])
-                }
+        self.report(ProgressEvent::FilesDiscovered { count: 1 });
+        let result = self
+            .read_text(file_path, &rel_path, false)
+            .and_then(|text| self.create_processed_file(file_path, &rel_path, base_dir, text));
+        self.report(ProgressEvent::FileProcessed {
+            rel_path: rel_path.clone(),
+        });
+        match result {
+            Ok(file) => Ok(vec![file]),
+            Err(_) if !self.context.file_system.path_exists(file_path) => {
+                self.handle_missing_file(&rel_path)?;
+                Ok(Vec::new())
             }
             Err(e) => {
-                debug!("Failed to read {rel_path}: {e}");
+                debug!("Skipping {rel_path}: {e}");
                 // Skip files that can't be read instead of failing
                 Ok(Vec::new())
             }
         }
     }
 
+    /// A file seen by the walk was gone by the time it was read. Skip it with a
+    /// warning, or fail the run when `fail_on_missing` is set.
+    fn handle_missing_file(&self, rel_path: &str) -> Result<()> {
+        if self.context.processing_config.fail_on_missing {
+            return Err(anyhow!(
+                "File disappeared before it could be read: {}",
+                rel_path
+            ));
+        }
+        warn!(
+            "File disappeared before it could be read, skipping: {}",
+            rel_path
+        );
+        Ok(())
+    }
+
     /// Process all files in a directory
     fn process_directory(&self, dir_path: &Path, base_dir: &Path) -> Result<Vec<ProcessedFile>> {
         let mut processed_files = Vec::new();
@@ -214,18 +380,45 @@ impl ParallelFileProcessor {
         // Build gitignore patterns
         let gitignore = self.build_gitignore(dir_path)?;
 
+        // When pinned to a ref, the .gitignore files from that commit replace the working tree's
+        let historical_gitignores = match &self.context.input_config.gitignore_ref {
+            Some(git_ref) => self.build_historical_gitignores(dir_path, git_ref)?,
+            None => Vec::new(),
+        };
+
         // Use parallel processing for directory contents
         let files_to_process: Vec<_> =
-            self.collect_files_to_process(dir_path, base_dir, &gitignore)?;
+            self.collect_files_to_process(dir_path, base_dir, &gitignore, &historical_gitignores)?;
+        self.report(ProgressEvent::FilesDiscovered {
+            count: files_to_process.len(),
+        });
 
-        // Process files in parallel with proper synchronization
+        // Process files in parallel with proper synchronization. Files a `!pattern`
+        // re-included may be read as text regardless of binary detection.
+        let reincluded_as_text = self.context.processing_config.reincluded_as_text;
         let results: Vec<Result<ProcessedFile>> = files_to_process
             .par_iter()
-            .map(|(path, rel_path)| self.process_file_with_priority(path, rel_path, base_dir))
+            .map(|(path, rel_path)| {
+                let force_text =
+                    reincluded_as_text && gitignore.matched(path, false).is_whitelist();
+                let result = self.process_file_with_priority(path, rel_path, base_dir, force_text);
+                self.report(ProgressEvent::FileProcessed {
+                    rel_path: rel_path.clone(),
+                });
+                result
+            })
             .collect();
 
-        // Filter out errors (e.g., binary files) and collect successful results
-        processed_files.extend(results.into_iter().filter_map(|r| r.ok()));
+        // Skip errors (e.g., binary files) and collect successful results
+        for (result, (path, rel_path)) in results.into_iter().zip(&files_to_process) {
+            match result {
+                Ok(file) => processed_files.push(file),
+                Err(_) if !self.context.file_system.path_exists(path) => {
+                    self.handle_missing_file(rel_path)?
+                }
+                Err(_) => {}
+            }
+        }
 
         Ok(processed_files)
     }
@@ -236,25 +429,94 @@ impl ParallelFileProcessor {
         dir_path: &Path,
         base_dir: &Path,
         gitignore: &Arc<ignore::gitignore::Gitignore>,
+        historical_gitignores: &[Gitignore],
     ) -> Result<Vec<(std::path::PathBuf, String)>> {
         let mut files_to_process = Vec::new();
 
         // Use ignore's walker for efficient directory traversal
         let mut walk_builder = ignore::WalkBuilder::new(dir_path);
+        let follow_symlinks = self.context.input_config.follow_symlinks;
         walk_builder
-            .follow_links(false)
+            .follow_links(follow_symlinks)
             .standard_filters(true)
-            .require_git(false);
+            .require_git(false)
+            .git_global(self.context.input_config.global_gitignore)
+            .git_exclude(self.context.input_config.global_gitignore)
+            // .yekignore files take precedence over .gitignore; ignore_patterns are checked below
+            .add_custom_ignore_filename(YEKIGNORE_FILE);
+        if self.context.input_config.gitignore_ref.is_some() {
+            walk_builder.git_ignore(false);
+        }
+        // Sorted so the path kept for a target reached twice doesn't depend on the filesystem
+        if follow_symlinks {
+            walk_builder.sort_by_file_name(|a, b| a.cmp(b));
+        }
+
+        // Prune excluded and vendored directories as whole subtrees instead of filtering their files
+        let input_config = &self.context.input_config;
+        let exclude_dirs = input_config.exclude_dirs.clone();
+        let skip_vendored = input_config.skip_vendored;
+        let unignore_patterns = input_config.unignore_patterns.clone();
+        // Never read back our own output when output_dir is inside the input
+        let output_subdir = input_config.output_dir.as_ref().and_then(|output_dir| {
+            let root = dir_path.canonicalize().ok()?;
+            output_dir
+                .strip_prefix(root)
+                .ok()
+                .filter(|rel| !rel.as_os_str().is_empty())
+                .map(Path::to_path_buf)
+        });
+        if !exclude_dirs.is_empty() || skip_vendored || output_subdir.is_some() {
+            let root = dir_path.to_path_buf();
+            walk_builder.filter_entry(move |entry| {
+                !entry.file_type().is_some_and(|ft| ft.is_dir())
+                    || !(is_excluded_dir(entry.path(), &root, &exclude_dirs)
+                        || output_subdir.as_deref().is_some_and(|output_subdir| {
+                            entry.path().strip_prefix(&root).ok() == Some(output_subdir)
+                        })
+                        || (skip_vendored
+                            && is_vendored_dir(entry.path(), &root, &unignore_patterns)))
+            });
+        }
 
         let gitignore = Arc::clone(gitignore);
 
+        // With symlinks followed, the same file or directory can be reached by
+        // several paths, or by a cycle. Only the first path to each canonical
+        // target is kept; the rest (and anything beneath them) are skipped.
+        let mut seen_targets = HashSet::new();
+        let mut skipped_dirs: Vec<PathBuf> = Vec::new();
+
         // Use sequential walking instead of parallel to avoid closure issues
         for result in walk_builder.build() {
             let entry = match result {
                 Ok(e) => e,
-                Err(_) => continue,
+                Err(e) => {
+                    debug!("Skipping walk entry: {e}");
+                    continue;
+                }
             };
 
+            if follow_symlinks {
+                if skipped_dirs.iter().any(|dir| entry.path().starts_with(dir)) {
+                    continue;
+                }
+                let is_new_target = entry
+                    .path()
+                    .canonicalize()
+                    .map_or(true, |target| seen_targets.insert(target));
+                if !is_new_target {
+                    debug!(
+                        "Skipping already visited target: {}",
+                        entry.path().display()
+                    );
+                    if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                        skipped_dirs.push(entry.path().to_path_buf());
+                    }
+                    continue;
+                }
+            }
+
             // Only process files
             if !entry.file_type().is_some_and(|ft| ft.is_file()) {
                 continue;
@@ -264,10 +526,20 @@ impl ParallelFileProcessor {
             let rel_path = self.normalize_path(&path, base_dir);
 
             // Check gitignore
-            if gitignore.matched(&path, false).is_ignore() {
+            if gitignore.matched(&path, false).is_ignore()
+                || is_ignored_by_any(&path, historical_gitignores)
+            {
                 debug!("Skipping ignored file: {rel_path}");
                 continue;
             }
+            if !self.is_included(&rel_path) {
+                debug!("Skipping file not matched by include_patterns: {rel_path}");
+                continue;
+            }
+            if self.is_excluded_language(&rel_path) {
+                debug!("Skipping file in an excluded language: {rel_path}");
+                continue;
+            }
 
             // Send to processing
             files_to_process.push((path, rel_path));
@@ -281,30 +553,162 @@ impl ParallelFileProcessor {
         &self,
         file_path: &Path,
         rel_path: &str,
-        _base_dir: &Path,
+        base_dir: &Path,
+        force_text: bool,
     ) -> Result<ProcessedFile> {
-        // Read file content
-        let content = self.context.file_system.read_file(file_path)?;
-
-        if inspect(&content) == ContentType::BINARY {
-            return Err(anyhow!("Binary file: {}", rel_path));
-        }
+        let content = self.read_text(file_path, rel_path, force_text)?;
 
-        // Calculate priority with category
-        let (priority, category) = self.calculate_priority_with_category(rel_path);
+        // Calculate priority with category, plus any per-directory baseline
+        let (priority, category) = self.file_priority(file_path, rel_path, base_dir);
 
         // Get thread-safe file index
         let file_index = self.get_next_file_index(priority);
 
         Ok(ProcessedFile::new_with_category(
             rel_path.to_string(),
-            String::from_utf8_lossy(&content).to_string(),
+            content,
             priority,
             file_index,
             category,
         ))
     }
 
+    /// Read and decode a file, failing for binary content unless `force_text`
+    /// is set. The text is an owned String, decoded before the buffer is reused.
+    fn read_text(&self, file_path: &Path, rel_path: &str, force_text: bool) -> Result<String> {
+        let max_file_size = self.context.processing_config.max_file_size;
+        let truncate = self.context.processing_config.truncate_oversize;
+        if let (Some(limit), false) = (max_file_size, truncate) {
+            let size = self.context.file_system.get_file_metadata(file_path)?.size;
+            if size > limit {
+                warn!(
+                    "Skipping {} ({}), larger than max_file_size ({})",
+                    rel_path,
+                    crate::format_bytes(size, &self.context.processing_config.size_display),
+                    crate::format_bytes(limit, &self.context.processing_config.size_display)
+                );
+                return Err(anyhow!("File too large: {}", rel_path));
+            }
+        }
+
+        // With a cache, a file unchanged since the last run keeps its binary
+        // verdict: binary files aren't opened and text files skip detection
+        let stamp = self.cache.as_ref().and_then(|_| {
+            let metadata = self.context.file_system.get_file_metadata(file_path).ok()?;
+            Some((mtime_nanos(metadata.modified), metadata.size))
+        });
+        let cached = match (&self.cache, stamp) {
+            (Some(cache), Some((_, size))) => cache.lookup(rel_path, size).cloned(),
+            _ => None,
+        };
+        let unchanged = cached
+            .as_ref()
+            .filter(|entry| stamp.is_some_and(|(mtime, _)| entry.mtime == mtime));
+        if let (Some(cache), Some(entry)) = (&self.cache, unchanged) {
+            if !entry.is_text && !force_text {
+                cache.record(rel_path, entry.clone());
+                return Err(anyhow!("Binary file: {}", rel_path));
+            }
+        }
+
+        let text = self.with_file_contents(file_path, |content| {
+            if force_text {
+                return Ok(Some(
+                    self.decode_content(content)
+                        .unwrap_or_else(|| String::from_utf8_lossy(content).into_owned()),
+                ));
+            }
+            // A file touched without being changed (new mtime, same size
+            // and hash) keeps its verdict as well
+            let hash = match unchanged {
+                Some(entry) => Some(entry.content_hash.clone()),
+                None => self.cache.as_ref().map(|_| content_hash(content)),
+            };
+            let known_text = cached
+                .as_ref()
+                .filter(|entry| Some(&entry.content_hash) == hash.as_ref())
+                .map(|entry| entry.is_text);
+            let text = match known_text {
+                Some(false) => None,
+                Some(true) => self.decode_content(content),
+                None if self.is_binary(content) => None,
+                None => self.decode_content(content),
+            };
+            if let (Some(cache), Some((mtime, size)), Some(hash)) = (&self.cache, stamp, hash) {
+                cache.record(
+                    rel_path,
+                    CacheEntry {
+                        mtime,
+                        size,
+                        is_text: text.is_some(),
+                        content_hash: hash,
+                    },
+                );
+            }
+            Ok(text)
+        })?;
+        let text = text.ok_or_else(|| anyhow!("Binary file: {}", rel_path))?;
+
+        match max_file_size {
+            Some(limit) if truncate => Ok(truncate_text(text, limit as usize)),
+            _ => Ok(text),
+        }
+    }
+
+    /// Read a file and hand its bytes to `f`, using this thread's pooled buffer
+    /// to avoid one allocation per file.
+    fn with_file_contents<T>(
+        &self,
+        file_path: &Path,
+        f: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<T> {
+        #[cfg(feature = "mmap")]
+        let f = match self.map_large_file(file_path) {
+            Some(map) => return f(&map),
+            None => f,
+        };
+
+        READ_BUFFER.with(|buffer| {
+            let mut buffer = buffer.borrow_mut();
+            buffer.clear();
+            let result = self
+                .context
+                .file_system
+                .read_file_into(file_path, &mut buffer)
+                .and_then(|()| f(&buffer));
+            if buffer.capacity() > MAX_POOLED_BUFFER_BYTES {
+                *buffer = Vec::new();
+            }
+            result
+        })
+    }
+
+    /// Memory-map a file at or above `mmap_threshold`. Returns `None` (so the
+    /// caller falls back to a normal read) when the file is below the threshold,
+    /// can't be mapped, or changed size while being mapped.
+    #[cfg(feature = "mmap")]
+    fn map_large_file(&self, file_path: &Path) -> Option<memmap2::Mmap> {
+        let threshold = self.context.processing_config.mmap_threshold?;
+        let file = std::fs::File::open(file_path).ok()?;
+        let len = file.metadata().ok()?.len();
+        if len == 0 || len < threshold {
+            return None;
+        }
+
+        // SAFETY: the map is read-only and only lives for a single file's processing.
+        // A concurrent truncation is detected by re-checking the length below; one
+        // that happens later still risks a fault, as with any mapped file.
+        let map = unsafe { memmap2::Mmap::map(&file) }.ok()?;
+        if map.len() as u64 != len || file.metadata().ok()?.len() != len {
+            debug!(
+                "File changed while mapping, falling back to read: {}",
+                file_path.display()
+            );
+            return None;
+        }
+        Some(map)
+    }
+
     /// Calculate priority for a file (legacy method for backward compatibility)
     #[allow(dead_code)]
     fn calculate_priority(&self, rel_path: &str) -> i32 {
@@ -359,6 +763,116 @@ impl ParallelFileProcessor {
         (priority, category)
     }
 
+    /// Binary check applied to every file read: content sniffing of the
+    /// leading sample, known signatures (when enabled), and mostly-invalid UTF-8.
+    fn is_binary(&self, content: &[u8]) -> bool {
+        let sample_len = content
+            .len()
+            .min(self.context.processing_config.binary_detection_bytes);
+        crate::looks_binary(&content[..sample_len])
+            || (self.context.processing_config.magic_detection && crate::has_binary_magic(content))
+            || self.is_mostly_invalid(content)
+    }
+
+    /// True if lossy decoding would replace more than the configured ratio of
+    /// characters with U+FFFD, i.e. the file is effectively binary.
+    fn is_mostly_invalid(&self, content: &[u8]) -> bool {
+        // Content with a BOM or a configured fallback encoding is checked by `decode_content`
+        if std::str::from_utf8(content).is_ok()
+            || Encoding::for_bom(content).is_some()
+            || self.context.processing_config.fallback_encoding.is_some()
+        {
+            return false;
+        }
+
+        let decoded = String::from_utf8_lossy(content);
+        let (replaced, total) = decoded.chars().fold((0usize, 0usize), |(r, t), c| {
+            (r + usize::from(c == char::REPLACEMENT_CHARACTER), t + 1)
+        });
+        total > 0
+            && replaced as f64 / total as f64 > self.context.processing_config.max_replacement_ratio
+    }
+
+    /// Decode file bytes to text: by their byte order mark (UTF-8, UTF-16LE,
+    /// UTF-16BE) if present, else as UTF-8, else with the `encoding` fallback,
+    /// else lossily as UTF-8. None when a BOM or the fallback encoding doesn't
+    /// decode cleanly, in which case the file is treated as binary.
+    fn decode_content(&self, content: &[u8]) -> Option<String> {
+        if let Some((encoding, bom_len)) = Encoding::for_bom(content) {
+            // A UTF-8 BOM is kept in the content, as it always has been
+            let body = if encoding == encoding_rs::UTF_8 {
+                content
+            } else {
+                &content[bom_len..]
+            };
+            return encoding
+                .decode_without_bom_handling_and_without_replacement(body)
+                .map(Cow::into_owned);
+        }
+        if let Ok(text) = std::str::from_utf8(content) {
+            return Some(text.to_string());
+        }
+        match self.context.processing_config.fallback_encoding {
+            Some(encoding) => encoding
+                .decode_without_bom_handling_and_without_replacement(content)
+                .map(Cow::into_owned),
+            None => Some(String::from_utf8_lossy(content).into_owned()),
+        }
+    }
+
+    /// Final priority of a file. An entry in the external scores file sets it
+    /// directly; otherwise rules, category, git boost and `.yekpriority` apply.
+    fn file_priority(
+        &self,
+        file_path: &Path,
+        rel_path: &str,
+        base_dir: &Path,
+    ) -> (i32, crate::category::FileCategory) {
+        if let Some(score) = self.context.processing_config.priority_scores.get(rel_path) {
+            return (*score, crate::category::categorize_file(rel_path));
+        }
+        let (priority, category) = self.calculate_priority_with_category(rel_path);
+        (
+            priority + self.directory_priority(file_path, base_dir),
+            category,
+        )
+    }
+
+    /// Baseline priority from the nearest `.yekpriority` file between the
+    /// file's directory and `base_dir`, or 0 when there is none.
+    fn directory_priority(&self, file_path: &Path, base_dir: &Path) -> i32 {
+        let bounded = !base_dir.as_os_str().is_empty() && base_dir != Path::new(".");
+        for dir in file_path.ancestors().skip(1) {
+            if bounded && !dir.starts_with(base_dir) {
+                break;
+            }
+            if let Some(score) = self.read_directory_priority(dir) {
+                return score;
+            }
+            if dir == base_dir {
+                break;
+            }
+        }
+        0
+    }
+
+    /// Read (and cache) the `.yekpriority` value for a single directory
+    fn read_directory_priority(&self, dir: &Path) -> Option<i32> {
+        let mut cache = self.directory_priorities.lock().unwrap();
+        if let Some(score) = cache.get(dir) {
+            return *score;
+        }
+
+        let score = self
+            .context
+            .file_system
+            .read_file(&dir.join(DIRECTORY_PRIORITY_FILE))
+            .ok()
+            .and_then(|bytes| String::from_utf8_lossy(&bytes).trim().parse::<i32>().ok());
+        cache.insert(dir.to_path_buf(), score);
+        score
+    }
+
     /// Calculate git boost for a file
     fn calculate_git_boost(
         &self,
@@ -392,7 +906,7 @@ impl ParallelFileProcessor {
     }
 
     /// Check if a file should be ignored
-    fn should_ignore_file(&self, file_path: &Path, _rel_path: &str) -> bool {
+    fn should_ignore_file(&self, file_path: &Path, rel_path: &str) -> bool {
         // Check ignore patterns
         let path_str = file_path.to_string_lossy();
         let ignored_by_pattern = self
@@ -409,35 +923,132 @@ impl ParallelFileProcessor {
             .map(|ext| self.context.input_config.binary_extensions.contains(ext))
             .unwrap_or(false);
 
-        ignored_by_pattern || is_binary
+        ignored_by_pattern
+            || is_binary
+            || !self.is_included(rel_path)
+            || self.is_excluded_language(rel_path)
+    }
+
+    /// True if the file's detected language is listed in `exclude_languages`
+    fn is_excluded_language(&self, rel_path: &str) -> bool {
+        is_language_excluded(rel_path, &self.context.input_config.exclude_languages)
+    }
+
+    /// True unless `include_patterns` are set and none of them matches the
+    /// file or one of its parent directories.
+    fn is_included(&self, rel_path: &str) -> bool {
+        self.include_matcher.as_ref().is_none_or(|matcher| {
+            matcher
+                .matched_path_or_any_parents(rel_path, false)
+                .is_ignore()
+        })
     }
 
     /// Build gitignore for a directory
     fn build_gitignore(&self, dir_path: &Path) -> Result<Arc<ignore::gitignore::Gitignore>> {
         let mut gitignore_builder = GitignoreBuilder::new(dir_path);
 
-        // Add custom patterns
-        for pattern in &self.context.input_config.ignore_patterns {
-            gitignore_builder.add_line(None, &pattern.to_string())?;
+        // Later lines win, so add from lowest to highest precedence. Built-in
+        // defaults go first so a `.gitignore` allowlist can still re-include them.
+        let (default_patterns, custom_patterns): (Vec<_>, Vec<_>) = self
+            .context
+            .input_config
+            .ignore_patterns
+            .iter()
+            .partition(|pattern| DEFAULT_IGNORE_PATTERNS.contains(&pattern.as_str()));
+        for pattern in default_patterns {
+            gitignore_builder.add_line(None, pattern.as_str())?;
         }
 
-        // Add .gitignore file if it exists
+        // Add .gitignore file if it exists (unless pinned to a ref's .gitignore)
         let gitignore_file = dir_path.join(".gitignore");
-        if self.context.file_system.path_exists(&gitignore_file) {
+        if self.context.input_config.gitignore_ref.is_none()
+            && self.context.file_system.path_exists(&gitignore_file)
+        {
             gitignore_builder.add(&gitignore_file);
         }
 
+        let yekignore_file = dir_path.join(YEKIGNORE_FILE);
+        if self.context.file_system.path_exists(&yekignore_file) {
+            gitignore_builder.add(&yekignore_file);
+        }
+
+        // Add custom patterns
+        for pattern in custom_patterns {
+            gitignore_builder.add_line(None, pattern.as_str())?;
+        }
+
         Ok(Arc::new(gitignore_builder.build()?))
     }
 
+    /// Build one matcher per `.gitignore` blob found in `git_ref`'s tree,
+    /// deepest directories first so nested rules take precedence.
+    fn build_historical_gitignores(
+        &self,
+        dir_path: &Path,
+        git_ref: &str,
+    ) -> Result<Vec<Gitignore>> {
+        let repo = git2::Repository::discover(dir_path).map_err(|e| {
+            anyhow!(
+                "'{}' is not inside a git repository: {}",
+                dir_path.display(),
+                e
+            )
+        })?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| anyhow!("Repository at '{}' has no working tree", dir_path.display()))?
+            .canonicalize()?;
+        let tree = repo
+            .revparse_single(git_ref)
+            .and_then(|obj| obj.peel_to_tree())
+            .map_err(|e| anyhow!("Failed to resolve '{}': {}", git_ref, e))?;
+
+        let mut gitignores = Vec::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.name() != Some(".gitignore") || entry.kind() != Some(git2::ObjectType::Blob) {
+                return git2::TreeWalkResult::Ok;
+            }
+            let blob = match repo.find_blob(entry.id()) {
+                Ok(blob) => blob,
+                Err(e) => {
+                    debug!("Failed to read {root}.gitignore at {git_ref}: {e}");
+                    return git2::TreeWalkResult::Ok;
+                }
+            };
+            let mut builder = GitignoreBuilder::new(workdir.join(root));
+            for line in String::from_utf8_lossy(blob.content()).lines() {
+                if let Err(e) = builder.add_line(None, line) {
+                    debug!("Invalid pattern in {root}.gitignore at {git_ref}: {e}");
+                }
+            }
+            match builder.build() {
+                Ok(gitignore) => gitignores.push(gitignore),
+                Err(e) => debug!("Failed to build {root}.gitignore at {git_ref}: {e}"),
+            }
+            git2::TreeWalkResult::Ok
+        })
+        .map_err(|e| anyhow!("Failed to walk tree at '{}': {}", git_ref, e))?;
+
+        gitignores
+            .sort_by_key(|gitignore| std::cmp::Reverse(gitignore.path().components().count()));
+        Ok(gitignores)
+    }
+
     /// Create a processed file with proper metadata
-    fn create_processed_file(&self, rel_path: &str, content: &[u8]) -> Result<ProcessedFile> {
-        let (priority, category) = self.calculate_priority_with_category(rel_path);
+    fn create_processed_file(
+        &self,
+        file_path: &Path,
+        rel_path: &str,
+        base_dir: &Path,
+        content: String,
+    ) -> Result<ProcessedFile> {
+        let (priority, category) = self.file_priority(file_path, rel_path, base_dir);
         let file_index = self.get_next_file_index(priority);
 
         Ok(ProcessedFile::new_with_category(
             rel_path.to_string(),
-            String::from_utf8_lossy(content).to_string(),
+            content,
             priority,
             file_index,
             category,
@@ -455,6 +1066,81 @@ impl ParallelFileProcessor {
     }
 }
 
+/// Cut `text` to at most `limit` bytes, at a char boundary, and mark how
+/// much was dropped
+fn truncate_text(mut text: String, limit: usize) -> String {
+    if text.len() <= limit {
+        return text;
+    }
+    let cut = (0..=limit)
+        .rev()
+        .find(|&i| text.is_char_boundary(i))
+        .unwrap_or(0);
+    let dropped = text.len() - cut;
+    text.truncate(cut);
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(&format!("... [truncated {} bytes]\n", dropped));
+    text
+}
+
+/// True if `dir` matches an `exclude_dir` rule: a bare name matches a directory
+/// of that name at any depth, a rule with a `/` matches the path relative to `root`.
+fn is_excluded_dir(dir: &Path, root: &Path, exclude_dirs: &[String]) -> bool {
+    let rel_path = dir.strip_prefix(root).unwrap_or(dir);
+    exclude_dirs.iter().any(|rule| {
+        let rule = rule.trim_matches('/');
+        if rule.contains('/') {
+            rel_path == Path::new(rule)
+        } else {
+            dir.file_name().is_some_and(|name| name == rule)
+        }
+    })
+}
+
+/// True if `dir` is a vendored dependency directory (see `VENDORED_DIRS`) that
+/// no unignore pattern points into. The walk root itself is never vendored.
+fn is_vendored_dir(dir: &Path, root: &Path, unignore_patterns: &[String]) -> bool {
+    let is_vendored = dir != root
+        && dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| VENDORED_DIRS.contains(&name));
+    if !is_vendored {
+        return false;
+    }
+    let rel_path = normalize_path(dir, root);
+    !unignore_patterns.iter().any(|pattern| {
+        let pattern = pattern.trim_start_matches("**/").trim_start_matches('/');
+        pattern == rel_path || pattern.starts_with(&format!("{}/", rel_path))
+    })
+}
+
+/// Check a path against matchers ordered deepest-first; the first matcher
+/// with an opinion (ignore or whitelist) decides.
+fn is_ignored_by_any(path: &Path, gitignores: &[Gitignore]) -> bool {
+    if gitignores.is_empty() {
+        return false;
+    }
+    let Ok(abs_path) = path.canonicalize() else {
+        return false;
+    };
+    for gitignore in gitignores {
+        if !abs_path.starts_with(gitignore.path()) {
+            continue;
+        }
+        let matched = gitignore.matched_path_or_any_parents(&abs_path, false);
+        if matched.is_ignore() {
+            return true;
+        }
+        if matched.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}
+
 /// Create a relative, slash-normalized path
 pub fn normalize_path(path: &Path, base: &Path) -> String {
     path.strip_prefix(base)
@@ -465,25 +1151,108 @@ pub fn normalize_path(path: &Path, base: &Path) -> String {
         .to_string()
 }
 
+/// Compile `include_patterns` into a matcher over relative paths, or None when
+/// there are none. Patterns use gitignore syntax (`**`, `{a,b}`, `[...]`), so a
+/// bare name like `Cargo.toml` matches at any depth.
+fn build_include_matcher(patterns: &[String]) -> Option<Gitignore> {
+    if patterns.is_empty() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new("");
+    for pattern in patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            debug!("Skipping invalid include pattern '{}': {}", pattern, e);
+        }
+    }
+    builder.build().ok()
+}
+
 /// Legacy function for backward compatibility - delegates to new implementation
 pub fn process_files_parallel(
     base_path: &Path,
     config: &crate::config::YekConfig,
     _boost_map: &HashMap<String, i32>,
 ) -> Result<Vec<ProcessedFile>> {
+    let processor = processor_for(base_path, config)?.with_cache(cache_for(base_path, config));
+    let files = processor.process_files_parallel(base_path)?;
+    processor.save_cache();
+    Ok(files)
+}
+
+/// Like `process_files_parallel`, reporting discovered and processed files
+/// to `progress`
+pub fn process_files_parallel_with_progress(
+    base_path: &Path,
+    config: &crate::config::YekConfig,
+    _boost_map: &HashMap<String, i32>,
+    progress: Option<&ProgressCallback>,
+) -> Result<Vec<ProcessedFile>> {
+    let processor = processor_for(base_path, config)?
+        .with_progress(progress.cloned())
+        .with_cache(cache_for(base_path, config));
+    let files = processor.process_files_parallel(base_path)?;
+    processor.save_cache();
+    Ok(files)
+}
+
+/// The file cache for `base_path` when `cache_dir` is set
+fn cache_for(base_path: &Path, config: &crate::config::YekConfig) -> Option<FileCache> {
+    config
+        .cache_dir
+        .as_deref()
+        .map(|cache_dir| FileCache::open(Path::new(cache_dir), base_path))
+}
+
+/// Yield the files under `base_path` one at a time instead of collecting
+/// them, for repositories too large to hold in memory at once
+pub fn process_files_stream(
+    base_path: &Path,
+    config: &crate::config::YekConfig,
+) -> Result<impl Iterator<Item = Result<ProcessedFile>>> {
+    processor_for(base_path, config)?.process_files_stream(base_path)
+}
+
+/// Count the files under `base_path` that would be packed, without reading them
+pub fn count_includable_files(
+    base_path: &Path,
+    config: &crate::config::YekConfig,
+) -> Result<usize> {
+    processor_for(base_path, config)?.count_includable_files(base_path)
+}
+
+fn processor_for(
+    base_path: &Path,
+    config: &crate::config::YekConfig,
+) -> Result<ParallelFileProcessor> {
     // This is a temporary bridge - in the final implementation,
     // this would be replaced with the new pipeline-based approach
-    let processor = ParallelFileProcessor::new(ProcessingContext::new(
+    Ok(ParallelFileProcessor::new(ProcessingContext::new(
         InputConfig {
             input_paths: config.input_paths.clone(),
             ignore_patterns: config
                 .ignore_patterns
                 .iter()
-                .map(|s| glob::Pattern::new(s).unwrap())
-                .collect(),
+                .map(|s| {
+                    glob::Pattern::new(s)
+                        .map_err(|e| anyhow!("ignore_patterns: Invalid pattern '{}': {}", s, e))
+                })
+                .collect::<Result<_>>()?,
             binary_extensions: config.binary_extensions.iter().cloned().collect(),
             max_git_depth: config.max_git_depth,
             git_boost_max: config.git_boost_max,
+            gitignore_ref: config.gitignore_at.clone(),
+            exclude_dirs: config.exclude_dir.clone(),
+            skip_vendored: !config.include_vendored,
+            unignore_patterns: config.unignore_patterns.clone(),
+            include_patterns: config.include_patterns.clone(),
+            exclude_languages: config.exclude_languages.clone(),
+            global_gitignore: !config.no_global_gitignore,
+            follow_symlinks: config.follow_symlinks,
+            output_dir: config
+                .output_dir
+                .as_deref()
+                .filter(|dir| !dir.is_empty())
+                .and_then(|dir| Path::new(dir).canonicalize().ok()),
         },
         OutputConfig::default(), // TODO: Convert from YekConfig
         ProcessingConfig {
@@ -494,10 +1263,35 @@ pub fn process_files_parallel(
             max_threads: None,
             memory_limit_mb: None,
             batch_size: 1000,
+            max_replacement_ratio: config.max_replacement_ratio,
+            magic_detection: !config.no_magic_detection,
+            fallback_encoding: config
+                .encoding
+                .as_deref()
+                .and_then(|label| Encoding::for_label(label.as_bytes())),
+            max_file_size: config
+                .max_file_size
+                .as_deref()
+                .and_then(|size| ByteSize::from_str(size).ok())
+                .map(|size| size.as_u64()),
+            truncate_oversize: config.oversize_behavior == "truncate",
+            size_display: config.size_display.clone(),
+            binary_detection_bytes: config
+                .binary_detection_bytes
+                .unwrap_or(crate::defaults::DEFAULT_BINARY_DETECTION_BYTES),
+            fail_on_missing: config.fail_on_missing,
+            reincluded_as_text: config.respect_gitignore_reinclude_as_text,
+            priority_scores: match &config.priority_scores_file {
+                Some(path) => crate::priority::load_priority_scores(Path::new(path))?,
+                None => HashMap::new(),
+            },
+            mmap_threshold: config
+                .mmap_threshold
+                .as_deref()
+                .and_then(|size| ByteSize::from_str(size).ok())
+                .map(|size| size.as_u64()),
         },
         crate::models::RepositoryInfo::new(base_path.to_path_buf(), false), // TODO: Proper repo info
         Arc::new(crate::repository::RealFileSystem),
-    ));
-
-    processor.process_files_parallel(base_path)
+    )))
 }
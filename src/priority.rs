@@ -1,15 +1,41 @@
 use git2;
 use regex;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 use tracing::debug;
 
+/// Name of the per-directory file holding a baseline priority (a single integer)
+/// that applies to every file beneath that directory.
+pub const DIRECTORY_PRIORITY_FILE: &str = ".yekpriority";
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct PriorityRule {
     pub pattern: String,
     pub score: i32,
 }
 
+/// Custom prioritization for library users. Higher scores are placed later
+/// in the output, closer to the prompt, just like rule scores.
+pub trait PriorityScorer: Sync {
+    /// Score a file from its relative path, content and last commit time (if known)
+    fn score(&self, path: &str, content: &str, git_time: Option<u64>) -> i32;
+}
+
+/// The built-in scorer: the sum of the scores of all matching priority rules
+#[derive(Debug, Clone, Default)]
+pub struct RuleBasedScorer {
+    pub rules: Vec<PriorityRule>,
+}
+
+impl PriorityScorer for RuleBasedScorer {
+    fn score(&self, path: &str, _content: &str, _git_time: Option<u64>) -> i32 {
+        get_file_priority(path, &self.rules)
+    }
+}
+
 /// Determine final priority of a file by scanning the priority list
 /// in descending order of score.
 pub fn get_file_priority(path: &str, rules: &[PriorityRule]) -> i32 {
@@ -47,6 +73,61 @@ pub fn get_file_priority_with_category(
     (total_priority, category)
 }
 
+/// Load externally computed priorities from a scores file. JSON files hold an
+/// object mapping paths to scores; anything else is read as `path,score` lines
+/// (an optional header line and `#` comments are skipped).
+pub fn load_priority_scores(path: &Path) -> anyhow::Result<HashMap<String, i32>> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        anyhow::anyhow!(
+            "priority_scores_file: Failed to read '{}': {}",
+            path.display(),
+            e
+        )
+    })?;
+
+    let entries: Vec<(String, i32)> = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str::<HashMap<String, i32>>(&contents)
+            .map_err(|e| anyhow::anyhow!("priority_scores_file: Invalid JSON: {}", e))?
+            .into_iter()
+            .collect()
+    } else {
+        let mut entries = Vec::new();
+        for (line_no, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((file, score)) = line.rsplit_once(',') else {
+                return Err(anyhow::anyhow!(
+                    "priority_scores_file: Expected 'path,score' on line {}",
+                    line_no + 1
+                ));
+            };
+            match score.trim().parse::<i32>() {
+                Ok(score) => entries.push((file.trim().to_string(), score)),
+                // Tolerate a header row such as "path,score"
+                Err(_) if entries.is_empty() && line_no == 0 => continue,
+                Err(e) => {
+                    return Err(anyhow::anyhow!(
+                        "priority_scores_file: Invalid score on line {}: {}",
+                        line_no + 1,
+                        e
+                    ))
+                }
+            }
+        }
+        entries
+    };
+
+    Ok(entries
+        .into_iter()
+        .map(|(file, score)| {
+            let file = file.replace('\\', "/");
+            (file.trim_start_matches("./").to_string(), score)
+        })
+        .collect())
+}
+
 /// Rank-based approach to compute how "recent" each file is (0=oldest, 1=newest).
 /// Then scale it to a user-defined or default max boost.
 pub fn compute_recentness_boost(
@@ -175,3 +256,109 @@ pub fn get_recent_commit_times_git2(
 
     Some(commit_times)
 }
+
+/// Window of history selected by `git_since`
+#[derive(Debug, Clone, PartialEq)]
+pub enum GitSince {
+    /// Commits reachable from HEAD but not from this ref, like `<ref>..HEAD`
+    Ref(String),
+    /// Commits made at or after this Unix time
+    Time(u64),
+}
+
+impl GitSince {
+    /// A date ("2024-05-01", midnight UTC) or an age ("30d", "6w") is a time;
+    /// anything else is taken as a ref.
+    pub fn parse(value: &str) -> Self {
+        if let Some(time) = parse_date(value) {
+            return GitSince::Time(time);
+        }
+        if let Ok(age) = crate::parse_age(value) {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            return GitSince::Time(now.saturating_sub(age));
+        }
+        GitSince::Ref(value.to_string())
+    }
+}
+
+/// Unix time of midnight UTC on a "YYYY-MM-DD" date
+fn parse_date(value: &str) -> Option<u64> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year = parts.next()?.parse().ok()?;
+    let month = parts.next()?.parse::<u8>().ok()?;
+    let day = parts.next()?.parse().ok()?;
+    let date =
+        time::Date::from_calendar_date(year, time::Month::try_from(month).ok()?, day).ok()?;
+    u64::try_from(date.midnight().assume_utc().unix_timestamp()).ok()
+}
+
+/// Time of the last commit that changed each file, among the `max_commits`
+/// most recent commits of the repository containing `path`, optionally limited
+/// to the `since` window. Keys are canonical absolute paths. Returns None when
+/// `path` is not inside a git repository, and an error when the `since` ref
+/// doesn't resolve.
+pub fn get_last_change_times_git2(
+    path: &Path,
+    max_commits: usize,
+    since: Option<&GitSince>,
+) -> anyhow::Result<Option<HashMap<PathBuf, u64>>> {
+    let Ok(repo) = git2::Repository::discover(path) else {
+        return Ok(None);
+    };
+    let Some(workdir) = repo.workdir().and_then(|dir| dir.canonicalize().ok()) else {
+        return Ok(None);
+    };
+
+    let mut revwalk = repo.revwalk()?;
+    if revwalk.push_head().is_err() {
+        return Ok(None);
+    }
+    revwalk.set_sorting(git2::Sort::TIME)?;
+    let mut cutoff = 0;
+    match since {
+        Some(GitSince::Ref(since_ref)) => {
+            let commit = repo
+                .revparse_single(since_ref)
+                .and_then(|obj| obj.peel_to_commit())
+                .map_err(|e| {
+                    anyhow::anyhow!("git_since: Failed to resolve '{}': {}", since_ref, e)
+                })?;
+            revwalk.hide(commit.id())?;
+        }
+        Some(GitSince::Time(time)) => cutoff = *time,
+        None => {}
+    }
+
+    let mut change_times = HashMap::new();
+    for oid in revwalk.take(max_commits).filter_map(|oid| oid.ok()) {
+        let Ok(commit) = repo.find_commit(oid) else {
+            continue;
+        };
+        // Commits come newest first, so everything after this is outside the window
+        if commit.time().seconds() < cutoff as i64 {
+            break;
+        }
+        let tree = commit.tree().ok();
+        let parent_tree = commit.parent(0).ok().and_then(|parent| parent.tree().ok());
+        let diff = match repo.diff_tree_to_tree(parent_tree.as_ref(), tree.as_ref(), None) {
+            Ok(diff) => diff,
+            Err(e) => {
+                debug!("Failed to diff commit {:?}: {:?}", oid, e);
+                continue;
+            }
+        };
+
+        // Newest commits come first, so the first time a path is seen is its last change
+        let time = commit.time().seconds() as u64;
+        for delta in diff.deltas() {
+            if let Some(file_path) = delta.new_file().path() {
+                change_times.entry(workdir.join(file_path)).or_insert(time);
+            }
+        }
+    }
+
+    Ok(Some(change_times))
+}
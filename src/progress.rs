@@ -0,0 +1,46 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Milestones of a run, reported to a [`ProgressCallback`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// The git history of an input directory was scanned for commit times;
+    /// `files` is how many files it dated
+    GitHistoryScanned { files: usize },
+    /// The walk found `count` more files to read
+    FilesDiscovered { count: usize },
+    /// A discovered file was read (or skipped as binary or unreadable)
+    FileProcessed { rel_path: String },
+    /// An output file was written
+    OutputWritten { path: PathBuf },
+}
+
+/// Receives progress events. Called from worker threads, so it must be
+/// `Send + Sync`.
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+/// A progress bar drawn on stderr, so it never mixes with streamed output.
+/// Hidden when stderr isn't a terminal, and cleared once dropped.
+pub fn stderr_progress_bar() -> ProgressBar {
+    let bar = ProgressBar::with_draw_target(Some(0), ProgressDrawTarget::stderr())
+        .with_finish(ProgressFinish::AndClear);
+    if let Ok(style) = ProgressStyle::with_template("{spinner} {msg} [{bar:30}] {pos}/{len} files")
+    {
+        bar.set_style(style.progress_chars("=> "));
+    }
+    bar.set_message("Scanning git history");
+    bar
+}
+
+/// Callback that advances `bar` as files are discovered and processed
+pub fn progress_bar_callback(bar: ProgressBar) -> ProgressCallback {
+    Arc::new(move |event| match event {
+        ProgressEvent::GitHistoryScanned { .. } => bar.set_message("Reading files"),
+        ProgressEvent::FilesDiscovered { count } => bar.inc_length(count as u64),
+        ProgressEvent::FileProcessed { .. } => bar.inc(1),
+        ProgressEvent::OutputWritten { path } => {
+            bar.set_message(format!("Wrote {}", path.display()))
+        }
+    })
+}
@@ -26,6 +26,12 @@ pub trait FileSystem {
     /// Read file contents as bytes
     fn read_file(&self, path: &Path) -> Result<Vec<u8>>;
 
+    /// Append file contents to an existing buffer, letting callers reuse allocations
+    fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(&self.read_file(path)?);
+        Ok(())
+    }
+
     /// Read directory entries
     fn read_directory(&self, path: &Path) -> Result<Vec<PathBuf>>;
 
@@ -71,6 +77,15 @@ impl FileSystem for RealFileSystem {
         fs::read(path).map_err(|e| anyhow!("Failed to read file '{}': {}", path.display(), e))
     }
 
+    fn read_file_into(&self, path: &Path, buf: &mut Vec<u8>) -> Result<()> {
+        use std::io::Read;
+
+        fs::File::open(path)
+            .and_then(|mut file| file.read_to_end(buf))
+            .map(|_| ())
+            .map_err(|e| anyhow!("Failed to read file '{}': {}", path.display(), e))
+    }
+
     fn read_directory(&self, path: &Path) -> Result<Vec<PathBuf>> {
         let mut entries = Vec::new();
         for entry in fs::read_dir(path)? {
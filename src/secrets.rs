@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::sync::OnceLock;
+
+use crate::config::YekConfig;
+use crate::defaults::SECRET_PATTERNS;
+use crate::models::ProcessedFile;
+
+/// A line that looks like it contains a secret
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretFinding {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    /// Name of the pattern that matched, e.g. `aws-access-key-id`
+    pub pattern: &'static str,
+}
+
+static COMPILED_PATTERNS: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+
+fn compiled_patterns() -> &'static [(&'static str, Regex)] {
+    COMPILED_PATTERNS.get_or_init(|| {
+        SECRET_PATTERNS
+            .iter()
+            .map(|(name, pattern)| {
+                (
+                    *name,
+                    Regex::new(pattern).expect("built-in secret pattern should compile"),
+                )
+            })
+            .collect()
+    })
+}
+
+/// Report every line of `files` matching a known secret pattern. Content is
+/// only inspected, never modified.
+pub fn scan_secrets(files: &[ProcessedFile]) -> Vec<SecretFinding> {
+    let patterns = compiled_patterns();
+    let mut findings = Vec::new();
+    for file in files {
+        for (index, line) in file.content.lines().enumerate() {
+            for (name, regex) in patterns {
+                if regex.is_match(line) {
+                    findings.push(SecretFinding {
+                        path: file.rel_path.clone(),
+                        line: index + 1,
+                        pattern: name,
+                    });
+                }
+            }
+        }
+    }
+    findings
+}
+
+/// Regexes to redact for `config`: the `secret_scan` patterns when
+/// `redact_secrets` is set, followed by `redact_patterns`
+pub fn redaction_patterns(config: &YekConfig) -> Result<Vec<Regex>> {
+    let builtin = compiled_patterns()
+        .iter()
+        .filter(|_| config.redact_secrets)
+        .map(|(_, regex)| regex.clone());
+    let custom = config.redact_patterns.iter().map(|pattern| {
+        Regex::new(pattern)
+            .map_err(|e| anyhow!("redact_patterns: Invalid pattern '{}': {}", pattern, e))
+    });
+    builtin.map(Ok).chain(custom).collect()
+}
+
+/// Replace every match of `patterns` in the files' content with
+/// `[REDACTED]`. Returns the files and the number of replacements made.
+pub fn redact_secrets(
+    files: Vec<ProcessedFile>,
+    patterns: &[Regex],
+) -> (Vec<ProcessedFile>, usize) {
+    let mut redactions = 0;
+    let files = files
+        .into_iter()
+        .map(|file| {
+            let mut content = None;
+            let mut count = 0;
+            for regex in patterns {
+                let current: &str = content.as_deref().unwrap_or(&file.content);
+                let found = regex.find_iter(current).count();
+                if found > 0 {
+                    count += found;
+                    content = Some(regex.replace_all(current, "[REDACTED]").into_owned());
+                }
+            }
+            let Some(content) = content else {
+                return file;
+            };
+            redactions += count;
+            ProcessedFile::new_with_category(
+                file.rel_path,
+                content,
+                file.priority,
+                file.file_index,
+                file.category,
+            )
+        })
+        .collect();
+    (files, redactions)
+}
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod comments_tests {
+    use yek::comments::strip_comments_for;
+
+    #[test]
+    fn test_strip_c_family_comments() {
+        let source =
+            "// header\nint x = 1; /* inline */\n/* multi\n   line */\nint y = 2; // trailing\n";
+        assert_eq!(strip_comments_for(source, "c"), "int x = 1;\nint y = 2;\n");
+    }
+
+    #[test]
+    fn test_strip_keeps_comment_markers_in_strings() {
+        let js = "const url = \"http://example.com\"; // link\nconst s = '/* no */';\nconst t = `a\n// b`;\n";
+        assert_eq!(
+            strip_comments_for(js, "js"),
+            "const url = \"http://example.com\";\nconst s = '/* no */';\nconst t = `a\n// b`;\n"
+        );
+
+        let py = "x = \"# not\"  # yes\ns = '''\n# kept\n'''\n";
+        assert_eq!(
+            strip_comments_for(py, "py"),
+            "x = \"# not\"\ns = '''\n# kept\n'''\n"
+        );
+
+        let sql = "SELECT '--x' FROM t -- why\n";
+        assert_eq!(strip_comments_for(sql, "sql"), "SELECT '--x' FROM t\n");
+    }
+
+    #[test]
+    fn test_strip_rust_lifetimes_chars_and_nested_blocks() {
+        let source = "/// Doc\nfn f<'a>(s: &'a str) -> char { /* a /* b */ c */ '/' }\nlet q = '\\''; // q\n";
+        assert_eq!(
+            strip_comments_for(source, "rs"),
+            "fn f<'a>(s: &'a str) -> char {  '/' }\nlet q = '\\'';\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_shell_and_yaml_need_space_before_hash() {
+        assert_eq!(
+            strip_comments_for("#!/bin/sh\n# note\necho $# \"#\" # gone\n", "sh"),
+            "#!/bin/sh\necho $# \"#\"\n"
+        );
+        assert_eq!(
+            strip_comments_for("name: it's here # gone\ntag: a#b\n", "yaml"),
+            "name: it's here\ntag: a#b\n"
+        );
+    }
+
+    #[test]
+    fn test_strip_leaves_unknown_extensions_untouched() {
+        let content = "// not code\n# heading\n";
+        assert_eq!(strip_comments_for(content, "md"), content);
+        assert_eq!(strip_comments_for(content, ""), content);
+    }
+
+    #[test]
+    fn test_strip_preserves_crlf() {
+        assert_eq!(
+            strip_comments_for("a = 1 # c\r\nb = 2\r\n", "toml"),
+            "a = 1\r\nb = 2\r\n"
+        );
+    }
+}
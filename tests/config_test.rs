@@ -39,6 +39,33 @@ fn test_validate_config_invalid_max_size() {
     assert!(result.unwrap_err().to_string().contains("max_size"));
 }
 
+#[test]
+fn test_validate_config_max_git_depth() {
+    let mut config =
+        YekConfig::extend_config_with_defaults(vec![".".to_string()], "output".to_string());
+    config.max_git_depth = -1;
+    let result = config.validate();
+    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("max_git_depth"));
+
+    // 0 means no git history at all
+    config.max_git_depth = 0;
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_validate_config_max_age_overflow() {
+    let mut config =
+        YekConfig::extend_config_with_defaults(vec![".".to_string()], "output".to_string());
+    config.max_age = Some("99999999999999999w".to_string());
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("max_age"), "{}", err);
+    assert!(err.contains("too large"), "{}", err);
+
+    config.max_age = Some("52w".to_string());
+    assert!(config.validate().is_ok());
+}
+
 #[test]
 fn test_validate_config_invalid_priority_rule_score() {
     let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
@@ -81,6 +108,39 @@ fn test_validate_config_invalid_ignore_pattern() {
     assert!(err.contains("Invalid pattern"));
 }
 
+#[test]
+fn test_validate_config_unclosed_brace_in_ignore_pattern() {
+    let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
+    config.ignore_patterns = vec!["src/{generated,vendor/**".to_string()];
+
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("ignore_patterns"));
+    assert!(err.contains("unclosed '{'"));
+}
+
+#[test]
+fn test_validate_config_unclosed_bracket_in_ignore_pattern() {
+    let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
+    config.ignore_patterns = vec!["logs/[0-9*.log".to_string()];
+
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.contains("ignore_patterns"));
+    assert!(err.contains("unclosed '['"));
+}
+
+#[test]
+fn test_validate_config_closed_glob_groups_are_accepted() {
+    let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
+    config.ignore_patterns = vec![
+        "src/{a,b}/*.rs".to_string(),
+        "[]]*.txt".to_string(),
+        "[!a-z]*.md".to_string(),
+        "\\{literal".to_string(),
+    ];
+
+    assert!(config.validate().is_ok());
+}
+
 #[test]
 fn test_validate_config_tree_header_mutual_exclusivity() {
     let mut config = YekConfig::extend_config_with_defaults(vec![], "/tmp/yek".to_string());
@@ -1380,3 +1440,101 @@ fn test_extract_download_url() {
     let result = YekConfig::extract_download_url(mock_json, "nonexistent-asset.tar.gz");
     assert!(result.is_err(), "Should fail when asset not found");
 }
+
+#[test]
+fn test_validate_config_rejects_unknown_format() {
+    let mut config =
+        YekConfig::extend_config_with_defaults(vec![".".to_string()], "output".to_string());
+    config.formats = vec!["markdown".to_string(), "json".to_string()];
+    assert!(config.validate().is_ok());
+
+    config.formats.push("html".to_string());
+    let err = config.validate().unwrap_err().to_string();
+    assert!(err.starts_with("formats: Unknown output format 'html'"));
+}
+
+#[test]
+fn test_validate_config_select() {
+    let mut config =
+        YekConfig::extend_config_with_defaults(vec![".".to_string()], "output".to_string());
+    config.select = Some("largest".to_string());
+    assert!(config.validate().is_err(), "select without select_count");
+
+    config.select_count = Some(3);
+    assert!(config.validate().is_ok());
+
+    config.select = Some("biggest".to_string());
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_validate_config_near_duplicate_threshold() {
+    let mut config =
+        YekConfig::extend_config_with_defaults(vec![".".to_string()], "output".to_string());
+    config.near_duplicate_threshold = Some(0.9);
+    assert!(config.validate().is_ok());
+
+    config.near_duplicate_threshold = Some(1.5);
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_builder_builds_validated_config() {
+    let config = YekConfig::builder()
+        .input_path("src")
+        .max_size("128K")
+        .token_mode(true)
+        .add_ignore("*.log")
+        .add_priority_rule("src/**", 100)
+        .stream(true)
+        .build()
+        .expect("valid builder config");
+
+    assert_eq!(config.input_paths, vec!["src".to_string()]);
+    assert!(config.token_mode);
+    assert_eq!(config.tokens, "128K");
+    assert_eq!(config.ignore_patterns, vec!["*.log".to_string()]);
+    assert_eq!(config.priority_rules[0].pattern, "src/**");
+    assert_eq!(config.priority_rules[0].score, 100);
+    assert_eq!(
+        config.output_template.as_deref(),
+        Some(DEFAULT_OUTPUT_TEMPLATE)
+    );
+}
+
+#[test]
+fn test_builder_rejects_invalid_config() {
+    let err = YekConfig::builder()
+        .input_path(".")
+        .add_priority_rule("src/**", 5000)
+        .stream(true)
+        .build()
+        .unwrap_err()
+        .to_string();
+    assert!(err.starts_with("priority_rules: Priority score 5000"));
+
+    let err = YekConfig::builder()
+        .max_size("0")
+        .stream(true)
+        .build()
+        .unwrap_err()
+        .to_string();
+    assert_eq!(err, "max_size: cannot be 0");
+}
+
+#[test]
+fn test_git_boost_max_range() {
+    let mut cfg = YekConfig::extend_config_with_defaults(
+        vec![".".to_string()],
+        std::env::temp_dir().to_string_lossy().to_string(),
+    );
+    for valid in [0, 100, 1000] {
+        cfg.git_boost_max = Some(valid);
+        assert!(cfg.validate().is_ok(), "{} should be valid", valid);
+    }
+    for invalid in [-1, 1001] {
+        cfg.git_boost_max = Some(invalid);
+        let err = cfg.validate().unwrap_err().to_string();
+        assert!(err.contains("git_boost_max"), "{}", err);
+    }
+}
@@ -486,4 +486,223 @@ mod e2e_tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_multiple_formats_in_one_run() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        let output_dir = temp_dir.path().join("output");
+        let repo_dir = temp_dir.path().join("repo");
+        fs::create_dir_all(repo_dir.join("src"))?;
+        fs::write(repo_dir.join("src/main.rs"), "fn main() {}")?;
+        fs::write(repo_dir.join("notes.txt"), "some notes")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .env("FORCE_TTY", "1")
+            .arg(&repo_dir)
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg("--formats")
+            .arg("markdown")
+            .arg("json")
+            .output()?;
+        assert!(output.status.success());
+
+        let mut written: Vec<_> = fs::read_dir(&output_dir)?
+            .map(|e| e.unwrap().path())
+            .collect();
+        written.sort();
+        assert_eq!(written.len(), 2, "Expected one file per format");
+
+        let json_path = written.iter().find(|p| p.extension().unwrap() == "json");
+        let md_path = written.iter().find(|p| p.extension().unwrap() == "md");
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(json_path.unwrap())?)?;
+        let markdown = fs::read_to_string(md_path.unwrap())?;
+
+        let mut json_files: Vec<String> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["filename"].as_str().unwrap().to_string())
+            .collect();
+        let mut md_files: Vec<String> = markdown
+            .lines()
+            .filter_map(|l| l.strip_prefix("## "))
+            .map(|l| l.to_string())
+            .collect();
+        json_files.sort();
+        md_files.sort();
+        assert_eq!(json_files, vec!["notes.txt", "src/main.rs"]);
+        assert_eq!(json_files, md_files);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fail_over_tokens_exit_code() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(
+            temp_dir.path().join("main.rs"),
+            "fn main() { println!(\"hello from a small repository\"); }\n",
+        )?;
+
+        let over = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--fail-over-tokens")
+            .arg("5")
+            .output()?;
+        assert_eq!(over.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&over.stderr).contains("exceeds the budget of 5 tokens"));
+        assert!(over.stdout.is_empty(), "no output should be written");
+
+        let under = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--fail-over-tokens")
+            .arg("100000")
+            .output()?;
+        assert!(under.status.success());
+        assert!(String::from_utf8_lossy(&under.stdout).contains("within the budget"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_only_prints_includable_file_count() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir_all(temp_dir.path().join("src"))?;
+        fs::write(temp_dir.path().join("src/main.rs"), "fn main() {}\n")?;
+        fs::write(temp_dir.path().join("src/lib.rs"), "pub fn lib() {}\n")?;
+        fs::write(temp_dir.path().join("README.md"), "# Readme\n")?;
+        // Excluded: the hidden .gitignore and the file it ignores
+        fs::write(temp_dir.path().join(".gitignore"), "*.tmp\n")?;
+        fs::write(temp_dir.path().join("scratch.tmp"), "scratch\n")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--count-only")
+            .output()?;
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "3");
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_root_uses_repo_root_from_subdir() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::create_dir_all(temp_dir.path().join(".git"))?;
+        fs::create_dir_all(temp_dir.path().join("crates/app"))?;
+        fs::write(temp_dir.path().join("root.txt"), "at the root\n")?;
+        fs::write(temp_dir.path().join("crates/app/main.rs"), "fn main() {}\n")?;
+        // A nested manifest must not stop the walk before the .git root
+        fs::write(temp_dir.path().join("crates/app/Cargo.toml"), "[package]\n")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path().join("crates/app"))
+            .arg("--auto-root")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(">>>> root.txt"));
+        assert!(stdout.contains(">>>> crates/app/main.rs"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_root_warning_respects_quiet() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("a.txt"), "alpha\n")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("--auto-root")
+            .output()?;
+        assert!(output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("No project root found"), "{}", stderr);
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .args(["--auto-root", "--quiet"])
+            .output()?;
+        assert!(output.status.success());
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("No project root found"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bom_prefixed_config_loads() -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = tempdir()?;
+        // Editors on Windows often save UTF-8 with a leading byte order mark
+        fs::write(
+            temp_dir.path().join("yek.toml"),
+            "\u{feff}output_template = \"#### FILE_PATH\\nFILE_CONTENT\"\nignore_patterns = [\"yek.toml\"]\n",
+        )?;
+        fs::write(temp_dir.path().join("notes.txt"), "hello\n")?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg("notes.txt")
+            .output()?;
+        assert!(
+            output.status.success(),
+            "stderr: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("#### notes.txt\nhello"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bom_prefixed_yaml_and_json_configs_load() -> Result<(), Box<dyn std::error::Error>> {
+        for (name, content) in [
+            ("yek.yaml", "\u{feff}line_numbers: true\n"),
+            ("yek.json", "\u{feff}{\"line_numbers\": true}\n"),
+        ] {
+            let temp_dir = tempdir()?;
+            fs::write(temp_dir.path().join(name), content)?;
+            fs::write(temp_dir.path().join("notes.txt"), "hello\n")?;
+
+            let output = Command::cargo_bin("yek")?
+                .current_dir(temp_dir.path())
+                .arg("notes.txt")
+                .output()?;
+            assert!(output.status.success());
+            assert!(
+                output.stderr.is_empty(),
+                "{}: {}",
+                name,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert!(String::from_utf8_lossy(&output.stdout).contains("  1 | hello"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_stream_at_cuts_output_with_warning() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let temp_dir = tempdir()?;
+        fs::write(temp_dir.path().join("big.txt"), "x".repeat(10_000))?;
+
+        let output = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .arg("--truncate-stream-at")
+            .arg("1KB")
+            .output()?;
+        assert!(output.status.success());
+        // 1000 bytes of output plus the trailing newline
+        assert_eq!(output.stdout.len(), 1001);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("exceeds truncate_stream_at"));
+
+        let unlimited = Command::cargo_bin("yek")?
+            .current_dir(temp_dir.path())
+            .arg(temp_dir.path())
+            .output()?;
+        assert!(unlimited.stdout.len() > 10_000);
+        assert!(!String::from_utf8_lossy(&unlimited.stderr).contains("truncate_stream_at"));
+        Ok(())
+    }
 }
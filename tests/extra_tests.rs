@@ -9,7 +9,7 @@ mod extra_tests {
     use yek::{
         concat_files,
         config::YekConfig,
-        is_text_file,
+        is_text_file, is_text_file_sampled, is_text_file_with_magic,
         parallel::process_files_parallel,
         priority::{compute_recentness_boost, get_file_priority},
         serialize_repo,
@@ -94,8 +94,8 @@ mod extra_tests {
         let stderr = String::from_utf8_lossy(&output.stderr);
 
         // Should contain both warnings
-        assert!(stderr.contains("Warning: Path 'definitely_nonexistent_path_12345' does not exist"));
-        assert!(stderr.contains("Warning: No files were processed. All specified paths were non-existent or contained no valid files."));
+        assert!(stderr.contains("Path 'definitely_nonexistent_path_12345' does not exist"));
+        assert!(stderr.contains("No files were processed. All specified paths were non-existent or contained no valid files."));
     }
 
     // Test process_files_parallel with an empty directory.
@@ -130,4 +130,68 @@ mod extra_tests {
             "File with a null byte should be detected as binary"
         );
     }
+
+    // Test that UTF-16 text with a byte order mark is text despite its interleaved nulls.
+    #[test]
+    fn test_is_text_file_utf16_with_bom() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("notes");
+        let mut content = vec![0xFF, 0xFE];
+        for unit in "Hello, UTF-16!\n".encode_utf16() {
+            content.extend_from_slice(&unit.to_le_bytes());
+        }
+        fs::write(&file_path, content).unwrap();
+        assert!(is_text_file(&file_path, &[]).unwrap());
+    }
+
+    // Test that an extensionless PNG is binary, by signature or by content.
+    #[test]
+    fn test_is_text_file_png_header() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("logo");
+        fs::write(
+            &file_path,
+            b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x00\x10\x00\x00\x00\x10\x08\x06",
+        )
+        .unwrap();
+        assert!(!is_text_file(&file_path, &[]).unwrap());
+        assert!(!is_text_file_with_magic(&file_path, &[], false).unwrap());
+    }
+
+    // Test that control-heavy content without a null byte is binary, and that
+    // only the sampled prefix is inspected.
+    #[test]
+    fn test_is_text_file_control_ratio_and_sample_size() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("program");
+        let mut content = b"#!header\n".to_vec();
+        content.extend((1u8..=8).cycle().take(64));
+        fs::write(&file_path, &content).unwrap();
+
+        assert!(!is_text_file(&file_path, &[]).unwrap());
+        assert!(is_text_file_sampled(&file_path, &[], true, 9).unwrap());
+        assert!(!is_text_file_sampled(&file_path, &[], true, 20).unwrap());
+    }
+
+    // Test that serialize_repo skips control-heavy files and honors binary_detection_bytes.
+    #[test]
+    fn test_serialize_repo_binary_detection_bytes() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join("readme.txt"), "plain text\n").unwrap();
+        let mut blob = "text prefix\n".repeat(4).into_bytes();
+        blob.extend((1u8..=8).cycle().take(256));
+        fs::write(temp_dir.path().join("blob"), &blob).unwrap();
+
+        let mut config = YekConfig::extend_config_with_defaults(
+            vec![temp_dir.path().to_string_lossy().to_string()],
+            "output".to_string(),
+        );
+        let (_, files) = serialize_repo(&config).unwrap();
+        let paths: Vec<_> = files.iter().map(|f| f.rel_path.as_str()).collect();
+        assert_eq!(paths, vec!["readme.txt"]);
+
+        config.binary_detection_bytes = Some(48);
+        let (_, files) = serialize_repo(&config).unwrap();
+        assert!(files.iter().any(|f| f.rel_path == "blob"));
+    }
 }
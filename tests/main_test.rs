@@ -221,6 +221,7 @@ fn test_main_with_output_name() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-name")
         .arg(&output_name)
@@ -259,6 +260,7 @@ fn test_main_non_streaming_mode() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-dir")
         .arg(temp_dir.path())
@@ -295,6 +297,7 @@ fn test_main_with_force_tty() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .env("FORCE_TTY", "1")
         .assert();
@@ -390,6 +393,7 @@ fn test_main_streaming_mode_with_debug() {
     // Test streaming mode with debug flag
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--debug")
         .arg("--output-name")
@@ -400,10 +404,7 @@ fn test_main_streaming_mode_with_debug() {
     cmd.success();
 
     // Check that output file was created
-    assert!(std::path::Path::new("output.txt").exists());
-
-    // Clean up
-    std::fs::remove_file("output.txt").ok();
+    assert!(temp_dir.path().join("output.txt").exists());
 }
 
 #[test]
@@ -419,6 +420,7 @@ fn test_main_checksum_error_handling() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-dir")
         .arg(temp_dir.path().join("output"))
@@ -440,6 +442,7 @@ fn test_main_file_write_failure_recovery() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-name")
         .arg(&output_name)
@@ -462,6 +465,7 @@ fn test_main_force_tty_environment() {
     // Test with FORCE_TTY environment variable
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-dir")
         .arg(temp_dir.path())
@@ -471,6 +475,126 @@ fn test_main_force_tty_environment() {
     cmd.success();
 }
 
+#[test]
+fn test_env_overrides_take_precedence_over_config_file() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let env_output_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+    fs::write(
+        temp_dir.path().join("yek.toml"),
+        "output-dir = \"from-config\"\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--no-global-config", "test.txt"])
+        .env("FORCE_TTY", "1")
+        .env("YEK_OUTPUT_DIR", env_output_dir.path())
+        .assert()
+        .success();
+    assert!(fs::read_dir(env_output_dir.path())
+        .unwrap()
+        .next()
+        .is_some());
+    assert!(!temp_dir.path().join("from-config").exists());
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--no-global-config", "test.txt"])
+        .env("FORCE_TTY", "1")
+        .env("YEK_STREAM", "true")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(">>>> test.txt\ncontent"));
+}
+
+#[test]
+fn test_invalid_priority_regex_is_reported() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("main.rs"), "fn main() {}").unwrap();
+    fs::write(
+        temp_dir.path().join("yek.toml"),
+        "[[priority-rules]]\npattern = \"*.rs\"\nscore = 10\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--no-global-config", "main.rs"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "priority_rules: Pattern '*.rs' is not a valid regex",
+        ));
+}
+
+#[test]
+fn test_compress_gzip_names_and_streams_compressed_output() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .args(["--compress", "gzip"])
+        .env("FORCE_TTY", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(".txt.gz\n"));
+
+    // Streamed output is a gzip stream, starting with its magic bytes
+    let output = Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .arg(temp_dir.path())
+        .args(["--compress", "gzip"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.starts_with(&[0x1f, 0x8b]));
+}
+
+#[test]
+fn test_progress_keeps_streamed_stdout_clean() {
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    let output = Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .arg("--progress")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        ">>>> test.txt\ncontent\n"
+    );
+}
+
 #[test]
 fn test_main_with_missing_output_dir_fallback() {
     use std::fs;
@@ -482,6 +606,7 @@ fn test_main_with_missing_output_dir_fallback() {
     // Test with an output directory that might fail to create
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-dir")
         .arg("/nonexistent/deeply/nested/path/that/cannot/be/created")
@@ -505,6 +630,7 @@ fn test_output_dir_and_output_name_combination() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-dir")
         .arg(&output_dir)
@@ -532,6 +658,7 @@ fn test_output_name_only_no_output_dir() {
 
     let cmd = Command::cargo_bin("yek")
         .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
         .arg(temp_dir.path())
         .arg("--output-name")
         .arg("standalone-output.txt")
@@ -556,3 +683,455 @@ fn test_main_help_includes_update_flag() {
         .stdout(predicate::str::contains("--update"))
         .stdout(predicate::str::contains("Update yek to the latest version"));
 }
+
+#[cfg(feature = "lock")]
+#[test]
+fn test_locked_output_dir_fails_clearly() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    // Simulate a concurrent run holding the lock
+    let lock = yek::lock_output_dir(&output_dir.path().to_string_lossy()).unwrap();
+
+    // FORCE_TTY writes to output_dir instead of streaming to the captured stdout
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .env("FORCE_TTY", "1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("is locked by another yek run"));
+
+    // Once released, a run succeeds and leaves only its output behind
+    drop(lock);
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .env("FORCE_TTY", "1")
+        .assert()
+        .success();
+    assert_eq!(fs::read_dir(output_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_show_config_source_reports_discovered_file_or_defaults() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--show-config-source", "--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Config source: defaults (no config file found)",
+        ));
+
+    fs::write(temp_dir.path().join("yek.toml"), "max_size = \"1MB\"\n").unwrap();
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--show-config-source", "--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stderr(
+            predicate::str::contains("Config source: ").and(predicate::str::contains("yek.toml")),
+        );
+}
+
+#[test]
+fn test_nested_project_configs_are_layered() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let package_dir = temp_dir.path().join("packages/app");
+    fs::create_dir_all(&package_dir).unwrap();
+    fs::write(
+        temp_dir.path().join("yek.toml"),
+        "ignore_patterns = [\"*.log\"]\nline_numbers = true\n",
+    )
+    .unwrap();
+    fs::write(package_dir.join("yek.toml"), "line_numbers = false\n").unwrap();
+    fs::write(package_dir.join("main.rs"), "fn main() {}").unwrap();
+    fs::write(package_dir.join("debug.log"), "noise").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(&package_dir)
+        .args(["--show-config-source", "--no-global-config", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(">>>> main.rs\nfn main() {}"))
+        .stdout(predicate::str::contains("debug.log").not())
+        .stderr(predicate::str::contains("packages/app/yek.toml, "));
+}
+
+#[test]
+fn test_multiple_config_files_in_one_directory_warn_instead_of_exiting() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("yek.toml"), "line_numbers = true\n").unwrap();
+    fs::write(temp_dir.path().join("yek.yaml"), "json: true\n").unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1 | content"))
+        .stderr(predicate::str::contains("Multiple config files"));
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--quiet", "--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+}
+
+#[test]
+fn test_env_sits_between_flags_and_config_files() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("yek.toml"), "max_size = \"2MB\"\n").unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .env("YEK_MAX_SIZE", "5MB")
+        .args(["--debug", "--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("\"max_size\": \"5MB\""));
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .env("YEK_MAX_SIZE", "5MB")
+        .args([
+            "--debug",
+            "--no-global-config",
+            "--max-size",
+            "1KB",
+            "test.txt",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("\"max_size\": \"1KB\""));
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .env("YEK_MAX_SIZE", "5MB")
+        .args([
+            "--debug",
+            "--no-global-config",
+            "--max-size=3KB",
+            "test.txt",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("\"max_size\": \"3KB\""));
+}
+
+#[test]
+fn test_yml_config_is_discovered_and_parsed_as_yaml() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+    fs::write(
+        temp_dir.path().join("yek.yml"),
+        "output-template: \"== FILE_PATH ==\\nFILE_CONTENT\"\n",
+    )
+    .unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args(["--show-config-source", "--no-global-config", "test.txt"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("yek.yml"))
+        .stdout(predicate::str::contains("== test.txt ==\ncontent"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_write_command_runs_once_per_output_file() {
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .arg("--output-dir")
+        .arg(output_dir.path())
+        .args(["--formats", "text", "--formats", "markdown"])
+        .arg("--post-write-command")
+        .arg("echo run >> {file}.done")
+        .env("FORCE_TTY", "1")
+        .assert()
+        .success();
+
+    let mut markers: Vec<String> = fs::read_dir(output_dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".done"))
+        .collect();
+    markers.sort();
+    assert_eq!(
+        markers.len(),
+        2,
+        "one marker per written file: {:?}",
+        markers
+    );
+    assert!(markers[0].ends_with(".md.done"));
+    assert!(markers[1].ends_with(".txt.done"));
+    for marker in markers {
+        let runs = fs::read_to_string(output_dir.path().join(marker)).unwrap();
+        assert_eq!(runs, "run\n");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_post_write_command_passes_paths_with_shell_syntax_intact() {
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_root = tempdir().unwrap();
+    let output_dir = output_root.path().join("packed output; touch injected");
+    fs::write(temp_dir.path().join("test.txt"), "content").unwrap();
+
+    for command in ["echo run >> {file}.done", "echo run >> '{file}'.quoted"] {
+        Command::cargo_bin("yek")
+            .expect("Binary 'yek' not found")
+            .current_dir(temp_dir.path())
+            .arg(".")
+            .arg("--output-dir")
+            .arg(&output_dir)
+            .arg("--post-write-command")
+            .arg(command)
+            .env("FORCE_TTY", "1")
+            .assert()
+            .success();
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names.len(), 3, "{:?}", names);
+    assert!(names.iter().any(|name| name.ends_with(".txt.done")));
+    assert!(names.iter().any(|name| name.ends_with(".txt.quoted")));
+    assert!(!temp_dir.path().join("injected").exists());
+    assert!(!output_root.path().join("injected").exists());
+}
+
+#[test]
+fn test_global_gitignore_and_info_exclude_are_honored() {
+    use std::fs;
+    use tempfile::tempdir;
+
+    let repo = tempdir().unwrap();
+    let home = tempdir().unwrap();
+    std::process::Command::new("git")
+        .arg("init")
+        .current_dir(repo.path())
+        .output()
+        .unwrap();
+    fs::write(repo.path().join(".git/info/exclude"), "*.scratch\n").unwrap();
+    fs::create_dir_all(home.path().join("git")).unwrap();
+    fs::write(home.path().join("git/ignore"), "notes.local\n").unwrap();
+    fs::write(repo.path().join("main.rs"), "fn main() {}").unwrap();
+    fs::write(repo.path().join("main.rs.scratch"), "swap").unwrap();
+    fs::write(repo.path().join("notes.local"), "finder").unwrap();
+
+    let run = |extra: &[&str]| {
+        let output = Command::cargo_bin("yek")
+            .expect("Binary 'yek' not found")
+            .current_dir(repo.path())
+            .args(["--no-global-config", "."])
+            .args(extra)
+            .env("HOME", home.path())
+            .env("XDG_CONFIG_HOME", home.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let output = run(&[]);
+    assert!(output.contains(">>>> main.rs\n"));
+    assert!(!output.contains("main.rs.scratch"));
+    assert!(!output.contains("notes.local"));
+
+    let output = run(&["--no-global-gitignore"]);
+    assert!(output.contains("main.rs.scratch"));
+    assert!(output.contains("notes.local"));
+}
+
+#[test]
+fn test_dry_run_lists_files_without_writing_output() {
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a".repeat(60)).unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "b".repeat(60)).unwrap();
+
+    let output = Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .args(["--dry-run", "--max-size", "100B", "--output-dir"])
+        .arg(output_dir.path())
+        .env("FORCE_TTY", "1")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", stdout);
+    assert!(lines[1].ends_with("60 B  a.txt"), "{}", lines[1]);
+    assert!(
+        lines[2].ends_with("60 B  b.txt (over limit)"),
+        "{}",
+        lines[2]
+    );
+    assert_eq!(lines[3], "1 of 2 files would be packed (60 B of 100 B)");
+    assert_eq!(fs::read_dir(output_dir.path()).unwrap().count(), 0);
+
+    let output = Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .args(["--dry-run", "--max-size", "2KB", "--size-display", "bytes"])
+        .env("FORCE_TTY", "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.ends_with("2 of 2 files would be packed (120 bytes of 2000 bytes)\n"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_max_total_size_warning_uses_size_display() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a".repeat(1500)).unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "b".repeat(1500)).unwrap();
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .args([".", "--max-total-size", "2KiB"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Dropped the 1 lowest-priority files (1.5 KB) to stay within max_total_size (2.0 KB)",
+        ));
+}
+
+#[test]
+fn test_max_files_warns_about_dropped_files() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    let output_dir = tempdir().unwrap();
+    for name in ["a.txt", "b.txt", "c.txt"] {
+        fs::write(temp_dir.path().join(name), name).unwrap();
+    }
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path())
+        .args(["--max-files", "1", "--output-dir"])
+        .arg(output_dir.path())
+        .env("FORCE_TTY", "1")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Found 3 files, more than max_files (1); dropped the 2 lowest-priority files",
+        ));
+}
+
+#[test]
+fn test_quiet_silences_warnings() {
+    use predicates::prelude::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    let temp_dir = tempdir().unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+    let missing = temp_dir.path().join("missing");
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path().join("a.txt"))
+        .arg(&missing)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.txt"))
+        .stderr(predicate::str::contains("WARN").and(predicate::str::contains("does not exist")));
+
+    Command::cargo_bin("yek")
+        .expect("Binary 'yek' not found")
+        .current_dir(temp_dir.path())
+        .arg(temp_dir.path().join("a.txt"))
+        .arg(&missing)
+        .arg("--quiet")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("a.txt"))
+        .stderr(predicate::str::is_empty());
+}
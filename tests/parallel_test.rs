@@ -8,7 +8,7 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use tempfile::tempdir;
 use yek::config::YekConfig;
-use yek::parallel::process_files_parallel;
+use yek::parallel::{process_files_parallel, process_files_stream};
 
 #[cfg(unix)]
 fn make_unreadable(path: &Path) -> std::io::Result<()> {
@@ -566,3 +566,180 @@ fn test_process_files_parallel_with_channel_error_simulation() {
     let files = result.unwrap();
     assert_eq!(files.len(), 100);
 }
+
+#[test]
+fn test_pooled_read_buffers_match_file_contents() {
+    use std::sync::Arc;
+    use yek::models::{InputConfig, OutputConfig, ProcessingConfig, RepositoryInfo};
+    use yek::parallel::ParallelFileProcessor;
+    use yek::pipeline::ProcessingContext;
+    use yek::repository::RealFileSystem;
+
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let mut expected = Vec::new();
+    for i in 0..300 {
+        let dir = temp_dir.path().join(format!("dir{}", i % 7));
+        fs::create_dir_all(&dir).unwrap();
+        // Vary sizes so a short file read after a long one would expose stale bytes
+        let body = format!("file {}\n", i).repeat(1 + (i * 37) % 50);
+        fs::write(dir.join(format!("file{}.txt", i)), &body).unwrap();
+        expected.push((format!("dir{}/file{}.txt", i % 7, i), body));
+    }
+    expected.sort();
+
+    let processor = ParallelFileProcessor::new(ProcessingContext::new(
+        InputConfig::default(),
+        OutputConfig::default(),
+        ProcessingConfig::default(),
+        RepositoryInfo::new(temp_dir.path().to_path_buf(), false),
+        Arc::new(RealFileSystem),
+    ));
+    let mut files: Vec<(String, String)> = processor
+        .process_files_parallel(temp_dir.path())
+        .expect("process_files_parallel failed")
+        .into_iter()
+        .map(|f| (f.rel_path, f.content))
+        .collect();
+    files.sort();
+    assert_eq!(files, expected);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_large_file_packs_via_mmap() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    let content: String = (0..40_000)
+        .map(|i| format!("line {} of a large text file\n", i))
+        .collect();
+    fs::write(temp_dir.path().join("large.txt"), &content).unwrap();
+    fs::write(temp_dir.path().join("small.txt"), "small").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![temp_dir.path().to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.mmap_threshold = Some("64KB".to_string());
+    let boosts: HashMap<String, i32> = HashMap::new();
+
+    let mut result = process_files_parallel(temp_dir.path(), &config, &boosts)
+        .expect("process_files_parallel failed");
+    result.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].rel_path, "large.txt");
+    assert_eq!(result[0].content, content);
+    assert_eq!(result[1].content, "small");
+}
+
+/// Real file system where one file "disappears" after the walk has listed it
+struct VanishingFileSystem {
+    vanished: PathBuf,
+}
+
+impl yek::repository::FileSystem for VanishingFileSystem {
+    fn path_exists(&self, path: &Path) -> bool {
+        path != self.vanished && path.exists()
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+    fn is_directory(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>> {
+        if path == self.vanished {
+            anyhow::bail!("No such file or directory");
+        }
+        Ok(fs::read(path)?)
+    }
+    fn read_directory(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        yek::repository::RealFileSystem.read_directory(path)
+    }
+    fn get_file_metadata(&self, path: &Path) -> Result<yek::repository::FileMetadata> {
+        yek::repository::RealFileSystem.get_file_metadata(path)
+    }
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+    fn resolve_symlink(&self, path: &Path) -> Result<PathBuf> {
+        yek::repository::RealFileSystem.resolve_symlink(path)
+    }
+}
+
+#[test]
+fn test_file_removed_between_walk_and_read_is_skipped() {
+    use std::sync::Arc;
+    use yek::models::{InputConfig, OutputConfig, ProcessingConfig, RepositoryInfo};
+    use yek::parallel::ParallelFileProcessor;
+    use yek::pipeline::ProcessingContext;
+
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    fs::write(temp_dir.path().join("kept.txt"), "kept").unwrap();
+    fs::write(temp_dir.path().join("gone.txt"), "gone").unwrap();
+
+    let run = |fail_on_missing: bool| {
+        ParallelFileProcessor::new(ProcessingContext::new(
+            InputConfig::default(),
+            OutputConfig::default(),
+            ProcessingConfig {
+                fail_on_missing,
+                ..Default::default()
+            },
+            RepositoryInfo::new(temp_dir.path().to_path_buf(), false),
+            Arc::new(VanishingFileSystem {
+                vanished: temp_dir.path().join("gone.txt"),
+            }),
+        ))
+        .process_files_parallel(temp_dir.path())
+    };
+
+    let files = run(false).expect("missing files should be skipped");
+    let paths: Vec<&str> = files.iter().map(|f| f.rel_path.as_str()).collect();
+    assert_eq!(paths, vec!["kept.txt"]);
+
+    let err = run(true).expect_err("strict mode should fail");
+    assert!(err.to_string().contains("gone.txt"));
+}
+
+#[test]
+fn test_process_files_stream_matches_parallel() {
+    let temp_dir = tempdir().expect("failed to create temp dir");
+    fs::create_dir_all(temp_dir.path().join("src")).unwrap();
+    fs::write(temp_dir.path().join("src/lib.rs"), "pub fn f() {}").unwrap();
+    fs::write(temp_dir.path().join("b.txt"), "b").unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "a").unwrap();
+    fs::write(temp_dir.path().join("image.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+
+    let mut config = YekConfig::extend_config_with_defaults(
+        vec![temp_dir.path().to_string_lossy().to_string()],
+        ".".to_string(),
+    );
+    config.priority_rules = vec![yek::priority::PriorityRule {
+        pattern: "^src/".to_string(),
+        score: 50,
+    }];
+
+    let streamed: Vec<(String, String, i32)> = process_files_stream(temp_dir.path(), &config)
+        .expect("process_files_stream failed")
+        .map(|file| {
+            let file = file.expect("stream item failed");
+            (file.rel_path, file.content, file.priority)
+        })
+        .collect();
+    assert_eq!(
+        streamed
+            .iter()
+            .map(|(path, _, _)| path.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a.txt", "b.txt", "src/lib.rs"]
+    );
+
+    let mut collected: Vec<(String, String, i32)> =
+        process_files_parallel(temp_dir.path(), &config, &HashMap::new())
+            .unwrap()
+            .into_iter()
+            .map(|file| (file.rel_path, file.content, file.priority))
+            .collect();
+    collected.sort_by(|a, b| a.2.cmp(&b.2).then_with(|| a.0.cmp(&b.0)));
+    assert_eq!(streamed, collected);
+}
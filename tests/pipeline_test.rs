@@ -46,6 +46,15 @@ mod pipeline_tests {
             binary_extensions: HashSet::new(),
             max_git_depth: 100,
             git_boost_max: Some(100),
+            gitignore_ref: None,
+            exclude_dirs: Vec::new(),
+            skip_vendored: true,
+            unignore_patterns: Vec::new(),
+            include_patterns: Vec::new(),
+            exclude_languages: Vec::new(),
+            global_gitignore: true,
+            follow_symlinks: false,
+            output_dir: None,
         }
     }
 